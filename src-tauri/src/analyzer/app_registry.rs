@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How deep to look for `.app` bundles below each root (e.g. `/Applications/Utilities/*.app`)
+const MAX_APP_SEARCH_DEPTH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct InstalledApp {
+    pub bundle_id: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Mapping of bundle identifiers to installed applications
+#[derive(Debug, Default)]
+pub struct AppRegistry {
+    apps: HashMap<String, InstalledApp>,
+}
+
+impl AppRegistry {
+    /// Standard application folders for a user with the given home directory
+    pub fn default_roots(home: &Path) -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("/Applications"),
+            PathBuf::from("/System/Applications"),
+            home.join("Applications"),
+        ]
+    }
+
+    /// Scan `roots` for `.app` bundles and read their `Info.plist`
+    pub fn scan(roots: &[PathBuf]) -> Self {
+        let mut registry = Self::default();
+
+        for root in roots {
+            let mut walker = WalkDir::new(root)
+                .max_depth(MAX_APP_SEARCH_DEPTH)
+                .into_iter();
+
+            while let Some(entry) = walker.next() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        log::debug!("Skipping unreadable entry: {}", e);
+                        continue;
+                    }
+                };
+
+                if !is_app_bundle(entry.path()) {
+                    continue;
+                }
+
+                // Never descend into a bundle; nested helpers are covered by vendor matching
+                walker.skip_current_dir();
+
+                if let Some(app) = read_app_bundle(entry.path()) {
+                    registry.insert(app);
+                }
+            }
+        }
+
+        registry
+    }
+
+    pub fn insert(&mut self, app: InstalledApp) {
+        self.apps.insert(app.bundle_id.to_lowercase(), app);
+    }
+
+    pub fn len(&self) -> usize {
+        self.apps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+
+    /// Whether an app with exactly this bundle identifier is installed
+    pub fn is_installed(&self, bundle_id: &str) -> bool {
+        self.apps.contains_key(&bundle_id.to_lowercase())
+    }
+
+    pub fn get_app_name(&self, bundle_id: &str) -> Option<&str> {
+        self.apps
+            .get(&bundle_id.to_lowercase())
+            .map(|app| app.name.as_str())
+    }
+
+    /// Whether `bundle_id` belongs to an installed app
+    ///
+    /// Besides exact matches this accepts sub-identifiers such as
+    /// `com.vendor.App.helper`, and identifiers sharing the vendor prefix of an
+    /// installed app (`com.vendor.Updater` when `com.vendor.App` is present),
+    /// since helpers and updaters rarely ship as standalone bundles.
+    pub fn owns(&self, bundle_id: &str) -> bool {
+        let id = bundle_id.to_lowercase();
+        if self.apps.contains_key(&id) {
+            return true;
+        }
+
+        let vendor = vendor_prefix(&id);
+        self.apps.keys().any(|installed| {
+            id.starts_with(&format!("{}.", installed))
+                || (vendor.is_some() && vendor == vendor_prefix(installed))
+        })
+    }
+}
+
+fn is_app_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "app") && path.is_dir()
+}
+
+/// `com.vendor.App` -> `com.vendor`
+fn vendor_prefix(bundle_id: &str) -> Option<&str> {
+    let mut dots = bundle_id.match_indices('.');
+    let _ = dots.next()?;
+    let (second, _) = dots.next()?;
+    Some(&bundle_id[..second])
+}
+
+/// Read the bundle identifier and name from an app's `Contents/Info.plist`
+pub fn read_app_bundle(app_path: &Path) -> Option<InstalledApp> {
+    let info_plist = app_path.join("Contents/Info.plist");
+    let value = match plist::Value::from_file(&info_plist) {
+        Ok(value) => value,
+        Err(e) => {
+            log::debug!("Could not read {}: {}", info_plist.display(), e);
+            return None;
+        }
+    };
+    let dict = value.as_dictionary()?;
+
+    let bundle_id = dict.get("CFBundleIdentifier")?.as_string()?.to_string();
+    let name = ["CFBundleDisplayName", "CFBundleName"]
+        .iter()
+        .find_map(|key| dict.get(key).and_then(|v| v.as_string()))
+        .map(|s| s.to_string())
+        .or_else(|| {
+            app_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })?;
+
+    Some(InstalledApp {
+        bundle_id,
+        name,
+        path: app_path.to_path_buf(),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// Create a fixture `.app` bundle with a minimal XML `Info.plist`
+    pub(crate) fn create_app(dir: &Path, file_name: &str, bundle_id: &str, name: &str) {
+        let contents = dir.join(file_name).join("Contents");
+        fs::create_dir_all(&contents).unwrap();
+
+        let mut dict = plist::Dictionary::new();
+        dict.insert("CFBundleIdentifier".into(), bundle_id.into());
+        dict.insert("CFBundleName".into(), name.into());
+        plist::Value::Dictionary(dict)
+            .to_file_xml(contents.join("Info.plist"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_scan_reads_bundles() {
+        let apps = tempdir().unwrap();
+        create_app(
+            apps.path(),
+            "Slack.app",
+            "com.tinyspeck.slackmacgap",
+            "Slack",
+        );
+        create_app(
+            &apps.path().join("Utilities"),
+            "Tool.app",
+            "org.example.Tool",
+            "Tool",
+        );
+        // Bundle without a plist is ignored
+        fs::create_dir_all(apps.path().join("Broken.app/Contents")).unwrap();
+
+        let registry = AppRegistry::scan(&[apps.path().to_path_buf()]);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.is_installed("com.tinyspeck.slackmacgap"));
+        assert!(registry.is_installed("ORG.EXAMPLE.TOOL"));
+        assert_eq!(
+            registry.get_app_name("com.tinyspeck.slackmacgap"),
+            Some("Slack")
+        );
+    }
+
+    #[test]
+    fn test_owns_matches_helpers_and_vendor() {
+        let mut registry = AppRegistry::default();
+        registry.insert(InstalledApp {
+            bundle_id: "com.vendor.App".to_string(),
+            name: "App".to_string(),
+            path: PathBuf::from("/Applications/App.app"),
+        });
+
+        assert!(registry.owns("com.vendor.App"));
+        assert!(registry.owns("com.vendor.App.helper"));
+        assert!(registry.owns("com.vendor.Updater"));
+        assert!(!registry.owns("com.other.App"));
+    }
+
+    #[test]
+    fn test_vendor_prefix() {
+        assert_eq!(vendor_prefix("com.vendor.app"), Some("com.vendor"));
+        assert_eq!(vendor_prefix("com.vendor"), None);
+    }
+}
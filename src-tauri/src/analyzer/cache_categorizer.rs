@@ -0,0 +1,418 @@
+use crate::models::scan_result::{CacheCategoryType, SafetyLevel};
+use crate::scanner::age_in_days;
+use crate::scanner::cache_scanner::{
+    classify_cache_entry, is_protected_cache_name, BROWSER_CACHE_PATHS, TEMP_IN_USE_DAYS,
+};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// System locations that must never be removed, nor any of their ancestors
+const CRITICAL_SYSTEM_PATHS: &[&str] = &[
+    "/",
+    "/Applications",
+    "/Library",
+    "/System",
+    "/Users",
+    "/Volumes",
+    "/bin",
+    "/etc",
+    "/opt",
+    "/private",
+    "/sbin",
+    "/tmp",
+    "/usr",
+    "/var",
+];
+
+/// Folders in the home directory that may be cleaned inside but never removed
+/// themselves ("" is the home directory)
+const CRITICAL_HOME_PATHS: &[&str] = &[
+    "",
+    "Applications",
+    "Desktop",
+    "Documents",
+    "Downloads",
+    "Library",
+    "Library/Application Support",
+    "Library/Caches",
+    "Library/Containers",
+    "Library/Logs",
+    "Library/Preferences",
+    "Movies",
+    "Music",
+    "Pictures",
+];
+
+/// Home folders whose contents are never touched (credentials, iCloud Drive)
+const PROTECTED_HOME_PATHS: &[&str] = &["Library/Keychains", "Library/Mobile Documents"];
+
+/// Category and safety level for a path, with the rule that decided it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathAssessment {
+    pub category: Option<CacheCategoryType>,
+    pub safety: SafetyLevel,
+    pub reason: String,
+}
+
+impl PathAssessment {
+    fn new(category: Option<CacheCategoryType>, safety: SafetyLevel, reason: &str) -> Self {
+        Self {
+            category,
+            safety,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Directories treated as temporary storage
+pub fn temp_roots() -> Vec<PathBuf> {
+    vec![
+        std::env::temp_dir(),
+        PathBuf::from("/tmp"),
+        PathBuf::from("/private/tmp"),
+        PathBuf::from("/private/var/folders"),
+    ]
+}
+
+/// Work out what a path is and how safe it is to delete
+///
+/// This mirrors the rules the cache scanner applies so the cleaner can
+/// recheck a path it is handed without trusting the caller's classification.
+/// Symlinks above the final component are resolved first, and names are
+/// compared ignoring case as the default macOS volume format does.
+pub fn assess_path(path: &Path, home: &Path) -> PathAssessment {
+    if !path.is_absolute()
+        || path
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+    {
+        return PathAssessment::new(
+            None,
+            SafetyLevel::Protected,
+            "Path must be absolute and normalized",
+        );
+    }
+    let path = &resolve_parent(path);
+    let home = &fs::canonicalize(home).unwrap_or_else(|_| home.to_path_buf());
+
+    let is_critical = CRITICAL_SYSTEM_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .chain(CRITICAL_HOME_PATHS.iter().map(|p| home.join(p)))
+        .chain(temp_roots())
+        .any(|critical| starts_with_folded(&critical, path));
+    if is_critical {
+        return PathAssessment::new(None, SafetyLevel::Protected, "System or top-level folder");
+    }
+
+    let volume_root = strip_prefix_folded(path, Path::new("/Volumes"))
+        .is_some_and(|in_volumes| in_volumes.components().count() == 1);
+    if volume_root || is_mount_point(path) {
+        return PathAssessment::new(None, SafetyLevel::Protected, "Root of a mounted volume");
+    }
+
+    if let Some(relative) = strip_prefix_folded(path, home) {
+        return assess_home_path(relative);
+    }
+
+    if temp_roots()
+        .iter()
+        .any(|root| starts_with_folded(path, root))
+    {
+        let age = fs::symlink_metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(age_in_days);
+        return match age {
+            Some(age) if age >= TEMP_IN_USE_DAYS => PathAssessment::new(
+                Some(CacheCategoryType::Temporary),
+                SafetyLevel::Safe,
+                "Temporary file",
+            ),
+            _ => PathAssessment::new(
+                Some(CacheCategoryType::Temporary),
+                SafetyLevel::Caution,
+                "Recent temporary file may still be in use",
+            ),
+        };
+    }
+
+    if starts_with_folded(path, Path::new("/Volumes")) {
+        return PathAssessment::new(None, SafetyLevel::Unknown, "File on an external volume");
+    }
+
+    PathAssessment::new(
+        None,
+        SafetyLevel::Protected,
+        "Outside the user's home directory",
+    )
+}
+
+/// `path` with symlinks in its parent resolved; the final component is kept,
+/// since removing a symlink leaves its target alone
+pub fn resolve_parent(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent)
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether `path` is a directory on another device than its parent
+#[cfg(unix)]
+fn is_mount_point(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let Some(parent) = path.parent() else {
+        return true;
+    };
+    match (fs::symlink_metadata(path), fs::metadata(parent)) {
+        (Ok(metadata), Ok(parent)) => metadata.is_dir() && metadata.dev() != parent.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_mount_point(_path: &Path) -> bool {
+    false
+}
+
+/// The rest of `path` below `base`, comparing names without regard to case
+///
+/// Erring towards a match elsewhere only protects more.
+/// `Path::strip_prefix`, ignoring case as the default APFS volume does
+pub(crate) fn strip_prefix_folded<'a>(path: &'a Path, base: &Path) -> Option<&'a Path> {
+    let mut rest = path.components();
+    for component in base.components() {
+        if !eq_folded(rest.next()?.as_os_str(), component.as_os_str()) {
+            return None;
+        }
+    }
+    Some(rest.as_path())
+}
+
+pub(crate) fn starts_with_folded(path: &Path, base: &Path) -> bool {
+    strip_prefix_folded(path, base).is_some()
+}
+
+fn eq_folded(a: &OsStr, b: &OsStr) -> bool {
+    a == b || a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+fn assess_home_path(relative: &Path) -> PathAssessment {
+    if PROTECTED_HOME_PATHS
+        .iter()
+        .any(|p| starts_with_folded(relative, Path::new(p)))
+    {
+        return PathAssessment::new(None, SafetyLevel::Protected, "Credentials or iCloud data");
+    }
+
+    if BROWSER_CACHE_PATHS
+        .iter()
+        .any(|(browser_path, _, _)| starts_with_folded(relative, Path::new(browser_path)))
+    {
+        return PathAssessment::new(
+            Some(CacheCategoryType::Browser),
+            SafetyLevel::Safe,
+            "Browser cache",
+        );
+    }
+
+    if let Some(in_caches) = strip_prefix_folded(relative, Path::new("Library/Caches")) {
+        let name = in_caches
+            .components()
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .unwrap_or_default();
+        if is_protected_cache_name(&name) {
+            return PathAssessment::new(
+                Some(CacheCategoryType::Application),
+                SafetyLevel::Protected,
+                "Cache holds account or sync state",
+            );
+        }
+        let category = classify_cache_entry(&name);
+        return match category {
+            CacheCategoryType::System | CacheCategoryType::Developer => {
+                PathAssessment::new(Some(category), SafetyLevel::Caution, "Rebuildable cache")
+            }
+            _ => PathAssessment::new(Some(category), SafetyLevel::Safe, "Application cache"),
+        };
+    }
+
+    if starts_with_folded(relative, Path::new("Library/Logs")) {
+        return PathAssessment::new(Some(CacheCategoryType::Logs), SafetyLevel::Safe, "Log file");
+    }
+
+    if starts_with_folded(relative, Path::new("Library")) {
+        return PathAssessment::new(None, SafetyLevel::Caution, "Application data");
+    }
+
+    PathAssessment::new(None, SafetyLevel::Unknown, "User file")
+}
+
+/// Category name recorded in history, matching the `CacheCategoryType` variant names
+pub fn category_name(category: Option<&CacheCategoryType>) -> String {
+    match category {
+        Some(category) => format!("{:?}", category),
+        None => "Other".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_critical_paths_are_protected() {
+        let home = Path::new("/Users/test");
+        for path in [
+            "/",
+            "/System/Library",
+            "/Users",
+            "/Users/test",
+            "/Users/test/Library",
+        ] {
+            assert_eq!(
+                assess_path(Path::new(path), home).safety,
+                SafetyLevel::Protected,
+                "{} should be protected",
+                path
+            );
+        }
+        assert_eq!(
+            assess_path(Path::new("/usr/local/bin/tool"), home).safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess_path(Path::new("/Users/test/Library/Caches/../Keychains"), home).safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess_path(Path::new("relative/path"), home).safety,
+            SafetyLevel::Protected
+        );
+    }
+
+    #[test]
+    fn test_home_paths() {
+        let home = Path::new("/Users/test");
+        let assess = |p: &str| assess_path(&home.join(p), home);
+
+        let chrome = assess("Library/Caches/Google/Chrome/Default");
+        assert_eq!(chrome.category, Some(CacheCategoryType::Browser));
+        assert_eq!(chrome.safety, SafetyLevel::Safe);
+
+        let spotify = assess("Library/Caches/com.spotify.client");
+        assert_eq!(spotify.category, Some(CacheCategoryType::Application));
+        assert_eq!(spotify.safety, SafetyLevel::Safe);
+
+        assert_eq!(
+            assess("Library/Caches/Homebrew").safety,
+            SafetyLevel::Caution
+        );
+        assert_eq!(
+            assess("Library/Caches/CloudKit").safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess("Library/Keychains/login.keychain-db").safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess("Library/Logs/app.log").category,
+            Some(CacheCategoryType::Logs)
+        );
+        assert_eq!(
+            assess("Library/Application Support/App").safety,
+            SafetyLevel::Caution
+        );
+        assert_eq!(assess("Movies/clip.mp4").safety, SafetyLevel::Unknown);
+    }
+
+    #[test]
+    fn test_case_variants_are_protected() {
+        let home = Path::new("/Users/test");
+        let assess = |p: &str| assess_path(&home.join(p), home).safety;
+        assert_eq!(assess("library"), SafetyLevel::Protected);
+        assert_eq!(assess("library/Keychains"), SafetyLevel::Protected);
+        assert_eq!(
+            assess("LIBRARY/keychains/login.keychain-db"),
+            SafetyLevel::Protected
+        );
+        assert_eq!(assess("library/caches/cloudkit"), SafetyLevel::Protected);
+        assert_eq!(
+            assess_path(Path::new("/users/TEST"), home).safety,
+            SafetyLevel::Protected
+        );
+    }
+
+    #[test]
+    fn test_volume_roots_are_protected() {
+        let home = Path::new("/Users/test");
+        assert_eq!(
+            assess_path(Path::new("/Volumes/Backup"), home).safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess_path(Path::new("/volumes/Backup"), home).safety,
+            SafetyLevel::Protected
+        );
+        assert_eq!(
+            assess_path(Path::new("/Volumes/Backup/old.dmg"), home).safety,
+            SafetyLevel::Unknown
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mount_points_are_detected() {
+        assert!(is_mount_point(Path::new("/proc")));
+        assert!(!is_mount_point(Path::new("/proc/self")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_parent_is_resolved() {
+        let home = tempdir().unwrap();
+        let keychains = home.path().join("Library/Keychains");
+        fs::create_dir_all(&keychains).unwrap();
+        fs::write(keychains.join("login.keychain-db"), b"secret").unwrap();
+        std::os::unix::fs::symlink(home.path().join("Library"), home.path().join("lnk")).unwrap();
+
+        let through_link = home.path().join("lnk/Keychains");
+        assert_eq!(
+            resolve_parent(&through_link),
+            fs::canonicalize(&keychains).unwrap()
+        );
+        assert_eq!(
+            assess_path(&through_link, home.path()).safety,
+            SafetyLevel::Protected
+        );
+        // The link itself is a user file; removing it leaves Library alone
+        assert_eq!(
+            assess_path(&home.path().join("lnk"), home.path()).safety,
+            SafetyLevel::Unknown
+        );
+    }
+
+    #[test]
+    fn test_temp_paths() {
+        let home = Path::new("/Users/test");
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("fresh.tmp");
+        fs::write(&file, b"tmp").unwrap();
+
+        let assessment = assess_path(&file, home);
+        assert_eq!(assessment.category, Some(CacheCategoryType::Temporary));
+        assert_eq!(assessment.safety, SafetyLevel::Caution);
+    }
+
+    #[test]
+    fn test_category_name() {
+        assert_eq!(category_name(Some(&CacheCategoryType::Browser)), "Browser");
+        assert_eq!(category_name(None), "Other");
+    }
+}
@@ -0,0 +1,331 @@
+use crate::models::config::{DeveloperEnvironment, DeveloperTool, DeveloperToolType, UserProfile};
+use crate::utils::fs::get_size;
+use rayon::prelude::*;
+use std::path::PathBuf;
+
+/// Confidence above which the user is treated as a developer
+///
+/// Set so Xcode or Homebrew plus any one other tool, even git, qualifies
+/// while either of them alone does not; `test_threshold_needs_a_second_tool`
+/// holds it there as weights change.
+pub const DEVELOPER_CONFIDENCE_THRESHOLD: f32 = 0.15;
+
+/// A developer tool, the paths that show it is installed and the caches it keeps
+///
+/// Paths starting with `~/` are relative to the home directory and may contain
+/// glob patterns.
+struct ToolSpec {
+    name: &'static str,
+    tool_type: DeveloperToolType,
+    weight: f32,
+    indicators: &'static [&'static str],
+    cache_paths: &'static [&'static str],
+}
+
+const DEVELOPER_TOOLS: &[ToolSpec] = &[
+    ToolSpec {
+        name: "Xcode",
+        tool_type: DeveloperToolType::Xcode,
+        weight: 0.9,
+        indicators: &["/Applications/Xcode.app", "~/Library/Developer/Xcode"],
+        cache_paths: &[
+            "~/Library/Developer/Xcode/DerivedData",
+            "~/Library/Developer/Xcode/iOS DeviceSupport",
+            "~/Library/Developer/CoreSimulator/Caches",
+        ],
+    },
+    ToolSpec {
+        name: "Homebrew",
+        tool_type: DeveloperToolType::Homebrew,
+        weight: 0.8,
+        indicators: &["/opt/homebrew", "/usr/local/Homebrew"],
+        cache_paths: &["~/Library/Caches/Homebrew"],
+    },
+    ToolSpec {
+        name: "npm",
+        tool_type: DeveloperToolType::NodeNpm,
+        weight: 0.7,
+        indicators: &["~/.npm", "~/.nvm"],
+        cache_paths: &["~/.npm/_cacache", "~/.nvm/.cache"],
+    },
+    ToolSpec {
+        name: "pyenv",
+        tool_type: DeveloperToolType::Python,
+        weight: 0.7,
+        indicators: &["~/.pyenv", "~/.virtualenvs"],
+        cache_paths: &["~/Library/Caches/pip", "~/.cache/pip", "~/.pyenv/cache"],
+    },
+    ToolSpec {
+        name: "Cargo",
+        tool_type: DeveloperToolType::Rust,
+        weight: 0.7,
+        indicators: &["~/.cargo", "~/.rustup"],
+        cache_paths: &["~/.cargo/registry", "~/.cargo/git"],
+    },
+    ToolSpec {
+        name: "Ruby gems",
+        tool_type: DeveloperToolType::Ruby,
+        weight: 0.5,
+        indicators: &["~/.gem", "~/.rbenv", "~/.rvm"],
+        cache_paths: &["~/.gem/ruby/*/cache", "~/.rbenv/cache", "~/.bundle/cache"],
+    },
+    ToolSpec {
+        name: "Gradle/Maven",
+        tool_type: DeveloperToolType::Java,
+        weight: 0.6,
+        indicators: &["~/.gradle", "~/.m2"],
+        cache_paths: &[
+            "~/.gradle/caches",
+            "~/.gradle/wrapper/dists",
+            "~/.m2/repository",
+        ],
+    },
+    ToolSpec {
+        name: "Docker",
+        tool_type: DeveloperToolType::Docker,
+        weight: 0.6,
+        indicators: &["/Applications/Docker.app", "~/.docker"],
+        cache_paths: &["~/Library/Containers/com.docker.docker"],
+    },
+    ToolSpec {
+        name: "JetBrains IDEs",
+        tool_type: DeveloperToolType::IDE,
+        weight: 0.6,
+        indicators: &[
+            "~/Library/Application Support/JetBrains",
+            "~/Library/Caches/JetBrains",
+        ],
+        cache_paths: &["~/Library/Caches/JetBrains"],
+    },
+    // /usr/bin/git is a shim present on every Mac, so look for real installs
+    ToolSpec {
+        name: "git",
+        tool_type: DeveloperToolType::Git,
+        weight: 0.3,
+        indicators: &[
+            "~/.gitconfig",
+            "/Library/Developer/CommandLineTools/usr/bin/git",
+            "/opt/homebrew/bin/git",
+        ],
+        cache_paths: &[],
+    },
+];
+
+/// Cache locations of every known developer tool, as `~/`-relative patterns
+pub fn developer_cache_paths() -> impl Iterator<Item = &'static str> {
+    DEVELOPER_TOOLS
+        .iter()
+        .flat_map(|spec| spec.cache_paths.iter().copied())
+}
+
+/// Detects installed developer tools and how much their caches take up
+pub struct DeveloperDetector {
+    home: PathBuf,
+    root: PathBuf,
+    measure_caches: bool,
+}
+
+impl DeveloperDetector {
+    pub fn new(home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            root: PathBuf::from("/"),
+            measure_caches: true,
+        }
+    }
+
+    pub fn for_current_user() -> Option<Self> {
+        dirs::home_dir().map(Self::new)
+    }
+
+    /// Resolve absolute tool paths below `root` instead of `/`
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Skip walking the caches when only presence matters; sizes are then 0
+    pub fn with_cache_sizes(mut self, measure_caches: bool) -> Self {
+        self.measure_caches = measure_caches;
+        self
+    }
+
+    pub fn detect(&self) -> DeveloperEnvironment {
+        let detected: Vec<(f32, DeveloperTool)> = DEVELOPER_TOOLS
+            .par_iter()
+            .filter(|spec| spec.indicators.iter().any(|p| !self.resolve(p).is_empty()))
+            .map(|spec| (spec.weight, self.describe(spec)))
+            .collect();
+
+        let max_score: f32 = DEVELOPER_TOOLS.iter().map(|spec| spec.weight).sum();
+        let score: f32 = detected.iter().map(|(weight, _)| weight).sum();
+        let confidence = score / max_score;
+
+        DeveloperEnvironment {
+            is_developer: confidence > DEVELOPER_CONFIDENCE_THRESHOLD,
+            detected_tools: detected.into_iter().map(|(_, tool)| tool).collect(),
+            confidence,
+        }
+    }
+
+    fn describe(&self, spec: &ToolSpec) -> DeveloperTool {
+        let cache_paths: Vec<PathBuf> = spec
+            .cache_paths
+            .iter()
+            .flat_map(|p| self.resolve(p))
+            .collect();
+        let cache_size = if self.measure_caches {
+            cache_paths
+                .par_iter()
+                .map(|p| get_size(p).unwrap_or(0))
+                .sum()
+        } else {
+            0
+        };
+
+        DeveloperTool {
+            name: spec.name.to_string(),
+            tool_type: spec.tool_type.clone(),
+            cache_paths,
+            cache_size,
+        }
+    }
+
+    /// Existing paths matching a tool path pattern
+    fn resolve(&self, pattern: &str) -> Vec<PathBuf> {
+        let full = match pattern.strip_prefix("~/") {
+            Some(relative) => self.home.join(relative),
+            None => self.root.join(pattern.trim_start_matches('/')),
+        };
+        let text = full.to_string_lossy();
+        let Some(star) = text.find('*') else {
+            return if full.symlink_metadata().is_ok() {
+                vec![full.clone()]
+            } else {
+                vec![]
+            };
+        };
+
+        // Only the wildcard part is a pattern; the home path may contain `[` or `?`
+        let pattern = format!("{}{}", glob::Pattern::escape(&text[..star]), &text[star..]);
+        glob::glob(&pattern)
+            .map(|paths| paths.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl DeveloperEnvironment {
+    /// Profile to start with before the user picks one
+    pub fn suggested_profile(&self) -> UserProfile {
+        if self.is_developer {
+            UserProfile::Developer
+        } else {
+            UserProfile::Regular
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_filler;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_nothing_installed() {
+        let home = tempdir().unwrap();
+        let root = tempdir().unwrap();
+        let env = DeveloperDetector::new(home.path())
+            .with_root(root.path())
+            .detect();
+
+        assert!(!env.is_developer);
+        assert!(env.detected_tools.is_empty());
+        assert_eq!(env.confidence, 0.0);
+        assert_eq!(env.suggested_profile(), UserProfile::Regular);
+    }
+
+    #[test]
+    fn test_detects_tools_and_cache_sizes() {
+        let home = tempdir().unwrap();
+        let root = tempdir().unwrap();
+        fs::create_dir_all(root.path().join("Applications/Xcode.app")).unwrap();
+        fs::create_dir_all(root.path().join("opt/homebrew")).unwrap();
+        write_filler(
+            &home
+                .path()
+                .join("Library/Developer/Xcode/DerivedData/App-abc/Build/out.o"),
+            1000,
+        );
+        write_filler(&home.path().join(".cargo/registry/cache/crate.crate"), 300);
+        write_filler(&home.path().join(".gem/ruby/3.2.0/cache/rake.gem"), 50);
+        write_filler(&home.path().join(".gem/ruby/3.3.0/cache/json.gem"), 25);
+
+        let env = DeveloperDetector::new(home.path())
+            .with_root(root.path())
+            .detect();
+        let tool = |t: DeveloperToolType| {
+            env.detected_tools
+                .iter()
+                .find(|tool| tool.tool_type == t)
+                .unwrap()
+        };
+
+        assert!(env.is_developer);
+        assert_eq!(env.suggested_profile(), UserProfile::Developer);
+        assert_eq!(env.detected_tools.len(), 4);
+        assert_eq!(tool(DeveloperToolType::Xcode).cache_size, 1000);
+        assert_eq!(tool(DeveloperToolType::Homebrew).cache_size, 0);
+        assert_eq!(tool(DeveloperToolType::Rust).cache_size, 300);
+        assert_eq!(tool(DeveloperToolType::Ruby).cache_paths.len(), 2);
+        assert_eq!(tool(DeveloperToolType::Ruby).cache_size, 75);
+
+        let expected =
+            (0.9 + 0.8 + 0.7 + 0.5) / DEVELOPER_TOOLS.iter().map(|s| s.weight).sum::<f32>();
+        assert!((env.confidence - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_threshold_needs_a_second_tool() {
+        let detect = |tools: &[&str]| {
+            let home = tempdir().unwrap();
+            let root = tempdir().unwrap();
+            for tool in tools {
+                match *tool {
+                    "xcode" => fs::create_dir_all(root.path().join("Applications/Xcode.app")),
+                    "homebrew" => fs::create_dir_all(root.path().join("opt/homebrew")),
+                    "git" => fs::write(home.path().join(".gitconfig"), b"[user]"),
+                    _ => unreachable!(),
+                }
+                .unwrap();
+            }
+            DeveloperDetector::new(home.path())
+                .with_root(root.path())
+                .with_cache_sizes(false)
+                .detect()
+                .is_developer
+        };
+
+        assert!(!detect(&["xcode"]));
+        assert!(!detect(&["homebrew"]));
+        assert!(!detect(&["git"]));
+        assert!(detect(&["homebrew", "git"]));
+        assert!(detect(&["xcode", "git"]));
+        assert!(detect(&["xcode", "homebrew", "git"]));
+    }
+
+    #[test]
+    fn test_skipping_cache_sizes() {
+        let home = tempdir().unwrap();
+        write_filler(&home.path().join(".npm/_cacache/index"), 500);
+
+        let env = DeveloperDetector::new(home.path())
+            .with_root(home.path().join("root"))
+            .with_cache_sizes(false)
+            .detect();
+        assert_eq!(env.detected_tools.len(), 1);
+        assert_eq!(env.detected_tools[0].cache_size, 0);
+        assert_eq!(env.detected_tools[0].cache_paths.len(), 1);
+    }
+}
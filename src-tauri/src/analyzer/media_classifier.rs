@@ -0,0 +1,282 @@
+use crate::models::scan_result::MediaType;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Extension table, kept in sync with `MEDIA_TYPES` in `src/lib/constants.ts`
+///
+/// The frontend lists `dmg` under both Archive and Application and resolves
+/// it to Archive, so it appears only there. Audio has no `MediaType`
+/// counterpart and falls through to Other.
+const MEDIA_EXTENSIONS: &[(MediaType, &[&str])] = &[
+    (
+        MediaType::Video,
+        &["mp4", "mov", "avi", "mkv", "wmv", "flv", "webm", "m4v"],
+    ),
+    (
+        MediaType::Image,
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "tiff", "webp", "heic", "raw",
+        ],
+    ),
+    (
+        MediaType::Archive,
+        &["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "dmg", "iso"],
+    ),
+    (
+        MediaType::Document,
+        &[
+            "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "pages", "numbers", "keynote",
+        ],
+    ),
+    (MediaType::Application, &["app", "pkg"]),
+];
+
+/// Enough bytes to cover every signature below
+const SNIFF_LEN: usize = 18;
+
+/// Sizes of the BMP info header versions (OS/2 1.x through BITMAPV5HEADER)
+const BMP_DIB_HEADER_SIZES: &[u32] = &[12, 16, 40, 52, 56, 64, 108, 124];
+
+/// Universal binaries hold a few architectures; Java class files share their
+/// magic but store a version of 45 or more where the count would be
+const MAX_FAT_ARCHS: u32 = 20;
+
+/// Look up the media type for a file extension (case-insensitive)
+pub fn media_type_from_extension(extension: &str) -> Option<MediaType> {
+    let ext = extension.to_lowercase();
+    MEDIA_EXTENSIONS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&ext.as_str()))
+        .map(|(media_type, _)| media_type.clone())
+}
+
+/// Identify a media type from the leading bytes of a file
+pub fn media_type_from_magic(header: &[u8]) -> Option<MediaType> {
+    let starts = |sig: &[u8]| header.starts_with(sig);
+
+    // ISO base media (MP4, MOV, HEIC, AVIF, M4A): size, "ftyp", major brand
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        return match &header[8..12] {
+            b"heic" | b"heix" | b"hevc" | b"mif1" | b"msf1" | b"avif" => Some(MediaType::Image),
+            b"M4A " | b"M4B " => None,
+            _ => Some(MediaType::Video),
+        };
+    }
+
+    if header.len() >= 12 && starts(b"RIFF") {
+        return match &header[8..12] {
+            b"AVI " => Some(MediaType::Video),
+            b"WEBP" => Some(MediaType::Image),
+            _ => None,
+        };
+    }
+
+    // Matroska/WebM, Flash video and ASF/WMV
+    if starts(&[0x1A, 0x45, 0xDF, 0xA3]) || starts(b"FLV") || starts(&[0x30, 0x26, 0xB2, 0x75]) {
+        return Some(MediaType::Video);
+    }
+
+    if starts(&[0xFF, 0xD8, 0xFF])
+        || starts(&[0x89, b'P', b'N', b'G'])
+        || starts(b"GIF8")
+        || is_bmp(header)
+        || starts(b"II*\0")
+        || starts(b"MM\0*")
+    {
+        return Some(MediaType::Image);
+    }
+
+    if starts(b"%PDF") || starts(&[0xD0, 0xCF, 0x11, 0xE0]) {
+        return Some(MediaType::Document);
+    }
+
+    if starts(b"PK\x03\x04")
+        || starts(b"Rar!")
+        || starts(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C])
+        || starts(&[0x1F, 0x8B])
+        || starts(b"BZh")
+        || starts(&[0xFD, b'7', b'z', b'X', b'Z', 0x00])
+    {
+        return Some(MediaType::Archive);
+    }
+
+    // Mach-O (32/64-bit, either byte order), universal binaries and xar installer packages
+    if starts(&[0xFE, 0xED, 0xFA, 0xCE])
+        || starts(&[0xFE, 0xED, 0xFA, 0xCF])
+        || starts(&[0xCE, 0xFA, 0xED, 0xFE])
+        || starts(&[0xCF, 0xFA, 0xED, 0xFE])
+        || (starts(&[0xCA, 0xFE, 0xBA, 0xBE])
+            && be_u32(header, 4).is_some_and(|n| (1..MAX_FAT_ARCHS).contains(&n)))
+        || starts(b"xar!")
+    {
+        return Some(MediaType::Application);
+    }
+
+    None
+}
+
+/// "BM" alone starts plenty of text, so the file header must be plausible too:
+/// a size covering both headers, zeroed reserved bytes and a known info header
+fn is_bmp(header: &[u8]) -> bool {
+    header.starts_with(b"BM")
+        && le_u32(header, 2).is_some_and(|size| size >= 26)
+        && header.get(6..10) == Some(&[0, 0, 0, 0])
+        && le_u32(header, 14).is_some_and(|size| BMP_DIB_HEADER_SIZES.contains(&size))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Classify a file by combining its extension with magic-byte sniffing
+///
+/// Content wins over the extension so a renamed `.mp4` is still a Video. The
+/// exception is ZIP, which is also the container for Office documents, so a
+/// ZIP signature defers to a more specific extension.
+pub fn classify_file(path: &Path) -> MediaType {
+    let by_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(media_type_from_extension);
+
+    let by_magic = match read_header(path) {
+        Ok(header) => media_type_from_magic(&header),
+        Err(e) => {
+            log::debug!("Could not sniff {}: {}", path.display(), e);
+            None
+        }
+    };
+
+    match (by_magic, by_extension) {
+        (Some(MediaType::Archive), Some(ext)) => ext,
+        (Some(magic), _) => magic,
+        (None, Some(ext)) => ext,
+        (None, None) => MediaType::Other,
+    }
+}
+
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    const MP4_HEADER: &[u8] = b"\x00\x00\x00\x18ftypisom\x00\x00\x02\x00";
+
+    #[test]
+    fn test_media_type_from_extension() {
+        assert_eq!(media_type_from_extension("MP4"), Some(MediaType::Video));
+        assert_eq!(media_type_from_extension("heic"), Some(MediaType::Image));
+        assert_eq!(media_type_from_extension("dmg"), Some(MediaType::Archive));
+        assert_eq!(
+            media_type_from_extension("pkg"),
+            Some(MediaType::Application)
+        );
+        assert_eq!(media_type_from_extension("mp3"), None);
+    }
+
+    #[test]
+    fn test_media_type_from_magic() {
+        assert_eq!(media_type_from_magic(MP4_HEADER), Some(MediaType::Video));
+        assert_eq!(
+            media_type_from_magic(b"\x00\x00\x00\x18ftypheic"),
+            Some(MediaType::Image)
+        );
+        assert_eq!(
+            media_type_from_magic(b"\x89PNG\r\n\x1a\n"),
+            Some(MediaType::Image)
+        );
+        assert_eq!(
+            media_type_from_magic(b"%PDF-1.7"),
+            Some(MediaType::Document)
+        );
+        assert_eq!(
+            media_type_from_magic(b"PK\x03\x04rest"),
+            Some(MediaType::Archive)
+        );
+        assert_eq!(
+            media_type_from_magic(&[0xCF, 0xFA, 0xED, 0xFE]),
+            Some(MediaType::Application)
+        );
+        assert_eq!(media_type_from_magic(b"plain text"), None);
+    }
+
+    #[test]
+    fn test_bmp_needs_a_plausible_header() {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&70u32.to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&54u32.to_le_bytes());
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        assert_eq!(media_type_from_magic(&bmp), Some(MediaType::Image));
+
+        assert_eq!(media_type_from_magic(b"BMW owners manual"), None);
+        assert_eq!(media_type_from_magic(b"BM"), None);
+    }
+
+    #[test]
+    fn test_java_class_is_not_a_universal_binary() {
+        // Two architectures
+        assert_eq!(
+            media_type_from_magic(&[0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 2]),
+            Some(MediaType::Application)
+        );
+        // Class file version 52.0 (Java 8)
+        assert_eq!(
+            media_type_from_magic(&[0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify_renamed_video() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("holiday.dat");
+        File::create(&path).unwrap().write_all(MP4_HEADER).unwrap();
+
+        assert_eq!(classify_file(&path), MediaType::Video);
+    }
+
+    #[test]
+    fn test_classify_zip_container_defers_to_extension() {
+        let dir = tempdir().unwrap();
+        let docx = dir.path().join("report.docx");
+        File::create(&docx)
+            .unwrap()
+            .write_all(b"PK\x03\x04")
+            .unwrap();
+        let unknown = dir.path().join("bundle.bin");
+        File::create(&unknown)
+            .unwrap()
+            .write_all(b"PK\x03\x04")
+            .unwrap();
+
+        assert_eq!(classify_file(&docx), MediaType::Document);
+        assert_eq!(classify_file(&unknown), MediaType::Archive);
+    }
+
+    #[test]
+    fn test_classify_falls_back_to_extension_and_other() {
+        let dir = tempdir().unwrap();
+        let iso = dir.path().join("disk.iso");
+        File::create(&iso).unwrap().write_all(&[0u8; 32]).unwrap();
+        let text = dir.path().join("notes.txt");
+        File::create(&text).unwrap().write_all(b"hello").unwrap();
+
+        assert_eq!(classify_file(&iso), MediaType::Archive);
+        assert_eq!(classify_file(&text), MediaType::Other);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod app_registry;
+pub mod cache_categorizer;
+pub mod developer_detector;
+pub mod media_classifier;
+pub mod protection;
@@ -0,0 +1,367 @@
+use crate::analyzer::cache_categorizer::{starts_with_folded, strip_prefix_folded};
+use crate::analyzer::developer_detector::developer_cache_paths;
+use crate::models::config::UserProfile;
+use crate::models::scan_result::{CacheCategoryType, CacheScanResult, SafetyLevel};
+use crate::scanner::age_in_days;
+use crate::scanner::cache_scanner::classify_cache_entry;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Xcode DerivedData used within this many days is kept by the Developer profile
+pub const DEFAULT_DERIVED_DATA_KEEP_DAYS: u32 = 14;
+
+/// Relative to the home directory
+const DERIVED_DATA: &str = "Library/Developer/Xcode/DerivedData";
+
+/// Caches the Developer profile keeps because rebuilding them is slow or
+/// needs the network (path, reason)
+const DEVELOPER_KEPT_CACHES: &[(&str, &str)] = &[
+    (
+        "~/.cargo/registry",
+        "Cargo would download every crate again on the next build",
+    ),
+    (
+        "~/.cargo/git",
+        "Cargo would fetch git dependencies again on the next build",
+    ),
+    (
+        "~/.m2/repository",
+        "Maven would download every dependency again",
+    ),
+    (
+        "~/.gradle/caches",
+        "Gradle would download every dependency again",
+    ),
+    (
+        "~/Library/Caches/JetBrains",
+        "JetBrains IDEs would have to rebuild their indexes",
+    ),
+];
+
+/// Decides which paths the active profile keeps out of cleaning
+///
+/// Both paths inside a kept location and their ancestors are protected, since
+/// removing an ancestor would remove the kept location with it.
+pub struct ProtectionEngine {
+    home: PathBuf,
+    profile: UserProfile,
+    derived_data_keep_days: u32,
+}
+
+impl ProtectionEngine {
+    pub fn new(home: impl Into<PathBuf>, profile: UserProfile) -> Self {
+        Self {
+            home: home.into(),
+            profile,
+            derived_data_keep_days: DEFAULT_DERIVED_DATA_KEEP_DAYS,
+        }
+    }
+
+    pub fn for_current_user(profile: UserProfile) -> Option<Self> {
+        dirs::home_dir().map(|home| Self::new(home, profile))
+    }
+
+    /// Keep DerivedData folders used within `days` days under the Developer profile
+    pub fn with_derived_data_keep_days(mut self, days: u32) -> Self {
+        self.derived_data_keep_days = days;
+        self
+    }
+
+    pub fn is_protected(&self, path: &Path) -> bool {
+        self.protection_reason(path).is_some()
+    }
+
+    /// Why the active profile keeps `path`, or `None` if it may be cleaned
+    pub fn protection_reason(&self, path: &Path) -> Option<String> {
+        match &self.profile {
+            UserProfile::Regular => None,
+            UserProfile::Developer => self.developer_reason(path),
+            UserProfile::Custom(custom) => {
+                let protected_path = custom
+                    .protected_paths
+                    .iter()
+                    .find(|p| overlaps(&self.pattern(&p.to_string_lossy()), path));
+                if let Some(protected_path) = protected_path {
+                    return Some(format!(
+                        "You protected {} in your profile",
+                        protected_path.display()
+                    ));
+                }
+                if custom.protect_developer_caches && self.is_developer_cache(path) {
+                    return Some("Your profile protects developer caches".to_string());
+                }
+                None
+            }
+        }
+    }
+
+    /// Mark the items and categories of a cache scan the active profile keeps
+    pub fn apply(&self, result: &mut CacheScanResult) {
+        for category in &mut result.categories {
+            let mut reasons: Vec<String> = Vec::new();
+            for item in &mut category.items {
+                if let Some(reason) = self.protection_reason(&item.path) {
+                    item.safe_to_delete = SafetyLevel::Protected;
+                    item.description = Some(reason.clone());
+                    reasons.push(reason);
+                }
+            }
+
+            if !category.items.is_empty() && reasons.len() == category.items.len() {
+                reasons.dedup();
+                category.is_protected = true;
+                category.protection_reason = Some(match reasons.as_slice() {
+                    [reason] => reason.clone(),
+                    _ => "Every item is protected by your profile".to_string(),
+                });
+            }
+        }
+    }
+
+    fn developer_reason(&self, path: &Path) -> Option<String> {
+        let kept = DEVELOPER_KEPT_CACHES
+            .iter()
+            .find(|(kept, _)| overlaps(&self.pattern(kept), path));
+        if let Some((_, reason)) = kept {
+            return Some(reason.to_string());
+        }
+
+        let root = self.home.join(DERIVED_DATA);
+        let days = self.derived_data_keep_days;
+        match strip_prefix_folded(path, &root) {
+            // A single project folder, or something inside it
+            Some(relative) if relative.components().next().is_some() => {
+                let project = root.join(relative.components().next()?);
+                self.is_recent(&project)
+                    .then(|| format!("Xcode build data used in the last {} days", days))
+            }
+            // DerivedData itself or one of its ancestors
+            _ if starts_with_folded(&root, path) => fs::read_dir(&root)
+                .ok()?
+                .filter_map(|e| e.ok())
+                .any(|e| self.is_recent(&e.path()))
+                .then(|| format!("Contains Xcode build data used in the last {} days", days)),
+            _ => None,
+        }
+    }
+
+    fn is_recent(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(age_in_days)
+            .map_or(true, |age| age < self.derived_data_keep_days)
+    }
+
+    fn is_developer_cache(&self, path: &Path) -> bool {
+        if developer_cache_paths().any(|p| overlaps(&self.pattern(p), path)) {
+            return true;
+        }
+        // Developer tool folders directly inside ~/Library/Caches
+        let caches = self.home.join("Library/Caches");
+        strip_prefix_folded(path, &caches)
+            .and_then(|relative| relative.components().next())
+            .is_some_and(|name| {
+                classify_cache_entry(&name.as_os_str().to_string_lossy())
+                    == CacheCategoryType::Developer
+            })
+    }
+
+    /// Split a `~/`-relative or absolute path into components, where only
+    /// components after the home directory may hold wildcards
+    fn pattern(&self, path: &str) -> Vec<PatternComponent> {
+        let (base, rest) = match path.strip_prefix("~/") {
+            Some(rest) => (self.home.as_path(), rest),
+            None if path == "~" => (self.home.as_path(), ""),
+            None => (Path::new(""), path),
+        };
+        base.components()
+            .map(|c| PatternComponent::Literal(c.as_os_str().to_string_lossy().to_string()))
+            .chain(Path::new(rest).components().map(|c| match c {
+                Component::Normal(name) => PatternComponent::new(&name.to_string_lossy()),
+                other => PatternComponent::Literal(other.as_os_str().to_string_lossy().to_string()),
+            }))
+            .collect()
+    }
+}
+
+/// One component of a protected location, matched ignoring case as the
+/// default APFS volume does
+enum PatternComponent {
+    Literal(String),
+    Glob(glob::Pattern),
+}
+
+impl PatternComponent {
+    fn new(text: &str) -> Self {
+        match glob::Pattern::new(text) {
+            Ok(pattern) if text.contains(['*', '?', '[']) => Self::Glob(pattern),
+            _ => Self::Literal(text.to_string()),
+        }
+    }
+
+    fn matches(&self, component: &str) -> bool {
+        match self {
+            Self::Literal(text) => text.eq_ignore_ascii_case(component),
+            Self::Glob(pattern) => pattern.matches_with(
+                component,
+                glob::MatchOptions {
+                    case_sensitive: false,
+                    ..glob::MatchOptions::new()
+                },
+            ),
+        }
+    }
+}
+
+/// Whether `path` lies inside the location `pattern` describes, or contains it
+fn overlaps(pattern: &[PatternComponent], path: &Path) -> bool {
+    path.components()
+        .zip(pattern)
+        .all(|(c, p)| p.matches(&c.as_os_str().to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::CustomProfile;
+    use crate::models::scan_result::{CacheCategory, CacheItem};
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    fn make_dir(path: &Path, age_days: u64) {
+        fs::create_dir_all(path).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_days * 86_400);
+        File::open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    fn item(path: PathBuf) -> CacheItem {
+        CacheItem {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            size: 1,
+            age_days: None,
+            app_name: None,
+            bundle_id: None,
+            safe_to_delete: SafetyLevel::Caution,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_regular_profile_protects_nothing() {
+        let engine = ProtectionEngine::new("/Users/test", UserProfile::Regular);
+        assert!(!engine.is_protected(Path::new("/Users/test/.cargo/registry")));
+    }
+
+    #[test]
+    fn test_developer_profile() {
+        let home = tempdir().unwrap();
+        let derived = home.path().join("Library/Developer/Xcode/DerivedData");
+        make_dir(&derived.join("Old-abc"), 30);
+        make_dir(&derived.join("Fresh-def"), 1);
+
+        let engine = ProtectionEngine::new(home.path(), UserProfile::Developer);
+        assert!(engine.is_protected(&home.path().join(".cargo/registry/cache")));
+        assert!(engine.is_protected(&home.path().join(".cargo")));
+        assert!(!engine.is_protected(&home.path().join(".cargo/bin")));
+        assert!(!engine.is_protected(&home.path().join("Library/Caches/Homebrew")));
+
+        assert!(!engine.is_protected(&derived.join("Old-abc")));
+        assert!(!engine.is_protected(&derived.join("Old-abc/Build")));
+        let reason = engine
+            .protection_reason(&derived.join("Fresh-def"))
+            .unwrap();
+        assert!(reason.contains("14 days"));
+        assert!(engine.is_protected(&derived));
+        assert!(engine.is_protected(&home.path().join("Library/Developer")));
+
+        // The default APFS volume ignores case
+        assert!(engine.is_protected(&home.path().join(".CARGO/registry")));
+        assert!(engine.is_protected(
+            &home
+                .path()
+                .join("Library/Developer/xcode/DerivedData/Fresh-def")
+        ));
+
+        let lenient = ProtectionEngine::new(home.path(), UserProfile::Developer)
+            .with_derived_data_keep_days(0);
+        assert!(!lenient.is_protected(&derived));
+    }
+
+    #[test]
+    fn test_custom_profile() {
+        let home = Path::new("/Users/test");
+        let engine = ProtectionEngine::new(
+            home,
+            UserProfile::Custom(CustomProfile {
+                protect_developer_caches: true,
+                protected_paths: vec![PathBuf::from("~/Projects/app/target")],
+            }),
+        );
+
+        assert!(engine.is_protected(&home.join("Projects/app/target/debug")));
+        assert!(engine.is_protected(&home.join("Projects/app")));
+        assert!(!engine.is_protected(&home.join("Projects/other")));
+        assert!(engine.is_protected(&home.join("Library/Caches/Homebrew")));
+        assert!(engine.is_protected(&home.join(".gem/ruby/3.2.0/cache/rake.gem")));
+        assert!(engine.is_protected(&home.join("Library/Developer/Xcode/DerivedData/A")));
+        assert!(!engine.is_protected(&home.join("Library/Caches/com.spotify.client")));
+    }
+
+    #[test]
+    fn test_apply_marks_items_and_categories() {
+        let home = Path::new("/Users/test");
+        let caches = home.join("Library/Caches");
+        let mut result = CacheScanResult {
+            total_size: 3,
+            categories: vec![
+                CacheCategory {
+                    name: "Developer Caches".to_string(),
+                    category_type: CacheCategoryType::Developer,
+                    total_size: 2,
+                    items: vec![item(caches.join("Homebrew")), item(caches.join("pip"))],
+                    is_protected: false,
+                    protection_reason: None,
+                },
+                CacheCategory {
+                    name: "Application Caches".to_string(),
+                    category_type: CacheCategoryType::Application,
+                    total_size: 1,
+                    items: vec![item(caches.join("com.spotify.client"))],
+                    is_protected: false,
+                    protection_reason: None,
+                },
+            ],
+            scanned_at: 0,
+            incomplete: false,
+            skipped: vec![],
+        };
+
+        let engine = ProtectionEngine::new(
+            home,
+            UserProfile::Custom(CustomProfile {
+                protect_developer_caches: true,
+                protected_paths: vec![],
+            }),
+        );
+        engine.apply(&mut result);
+
+        let developer = &result.categories[0];
+        assert!(developer.is_protected);
+        assert_eq!(
+            developer.protection_reason.as_deref(),
+            Some("Your profile protects developer caches")
+        );
+        assert!(developer
+            .items
+            .iter()
+            .all(|i| i.safe_to_delete == SafetyLevel::Protected));
+        assert!(!result.categories[1].is_protected);
+        assert_eq!(
+            result.categories[1].items[0].safe_to_delete,
+            SafetyLevel::Caution
+        );
+    }
+}
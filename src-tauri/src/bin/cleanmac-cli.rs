@@ -0,0 +1,507 @@
+//! Headless front end to the CleanMac scanners and cleaner, for scripts and SSH sessions
+
+use clap::{Parser, Subcommand};
+use cleanmac_lib::analyzer::protection::ProtectionEngine;
+use cleanmac_lib::cleaner::history::HistoryStore;
+use cleanmac_lib::cleaner::safe_delete::{history_entry, Cleaner};
+use cleanmac_lib::models::config::AppConfig;
+use cleanmac_lib::models::history::{CleanMode, CleanPlan, CleaningResult, HistoryQuery};
+use cleanmac_lib::scanner::cache_scanner::CacheScanner;
+use cleanmac_lib::scanner::dir_index::DirIndexStore;
+use cleanmac_lib::scanner::duplicate_folder_scanner::DuplicateFolderScanner;
+use cleanmac_lib::scanner::duplicate_scanner::DuplicateScanner;
+use cleanmac_lib::scanner::hash_cache::{HashCache, HashCacheStore};
+use cleanmac_lib::scanner::large_file_scanner::LargeFileScanner;
+use cleanmac_lib::scanner::orphan_scanner::OrphanScanner;
+use cleanmac_lib::scanner::similar_image_scanner::SimilarImageScanner;
+use cleanmac_lib::utils::app_dirs::{app_config_dir, app_data_dir};
+use cleanmac_lib::utils::config_store::ConfigStore;
+use cleanmac_lib::utils::exclusions::ExclusionMatcher;
+use cleanmac_lib::utils::format::{format_bytes, format_relative_time};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(
+    name = "cleanmac-cli",
+    version,
+    about = "Find and clean reclaimable disk space"
+)]
+struct Cli {
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Look for reclaimable space
+    Scan {
+        #[command(subcommand)]
+        target: ScanTarget,
+    },
+    /// Move paths to the Trash, or delete them with --permanent
+    Clean {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Show what would be cleaned without touching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete instead of moving to the Trash
+        #[arg(long)]
+        permanent: bool,
+        /// Clean paths the active profile protects
+        #[arg(long)]
+        override_protection: bool,
+    },
+    /// Show past cleanings, newest first
+    History {
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScanTarget {
+    /// Caches, logs and temporary files
+    Caches,
+    /// Files above the size threshold
+    Large {
+        /// Overrides the configured threshold
+        #[arg(long)]
+        threshold_mb: Option<u64>,
+        /// Folders to scan instead of the configured ones
+        paths: Vec<PathBuf>,
+    },
+    /// Files with identical contents
+    Duplicates {
+        /// Find copied folders instead of single files
+        #[arg(long)]
+        folders: bool,
+        /// Find images that look alike instead of identical files
+        #[arg(long, conflicts_with = "folders")]
+        images: bool,
+        /// Hash bits similar images may differ in
+        #[arg(long, requires = "images")]
+        max_distance: Option<u32>,
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Library data left behind by uninstalled apps
+    Orphans,
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, String> {
+    let config = load_config()?;
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let json = cli.json;
+
+    match cli.command {
+        Command::Scan { target } => scan(target, config, &cwd, json)?,
+        Command::Clean {
+            paths,
+            dry_run,
+            permanent,
+            override_protection,
+        } => {
+            let paths = absolute(paths, &cwd);
+            let cleaner = build_cleaner(config, override_protection)?;
+            let mode = if permanent {
+                CleanMode::Permanent
+            } else {
+                CleanMode::Trash
+            };
+
+            if dry_run {
+                let plan = cleaner.plan(&paths, mode);
+                if json {
+                    print_json(&plan)?;
+                } else {
+                    print_plan(&plan);
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let result = cleaner.clean(&paths, mode);
+            if let Some(entry) = history_entry(&result) {
+                // Shared with the app, so cleanings from the CLI show up there too
+                let store = HistoryStore::in_dir(&data_dir()?);
+                if let Err(e) = store.append(entry) {
+                    log::warn!("Failed to record cleaning history: {}", e);
+                }
+            }
+            if json {
+                print_json(&result)?;
+            } else {
+                print_clean_result(&result);
+            }
+            if !result.success {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::History { limit, offset } => {
+            let store = HistoryStore::in_dir(&data_dir()?);
+            let page = store
+                .list(&HistoryQuery {
+                    limit,
+                    offset,
+                    ..HistoryQuery::default()
+                })
+                .map_err(|e| e.to_string())?;
+            if json {
+                print_json(&page)?;
+            } else {
+                print_table(
+                    &["ID", "WHEN", "ITEMS", "RECLAIMED", "CATEGORIES"],
+                    page.entries
+                        .iter()
+                        .map(|e| {
+                            vec![
+                                e.id.clone(),
+                                format_relative_time(e.timestamp),
+                                e.items_cleaned.to_string(),
+                                format_bytes(e.space_reclaimed),
+                                e.categories.join(", "),
+                            ]
+                        })
+                        .collect(),
+                );
+                println!("{} of {} entries", page.entries.len(), page.total);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Run `scan` with the persisted hash cache, saving it afterwards
+fn with_hash_cache<T>(scan: impl FnOnce(Arc<HashCache>) -> T) -> Result<T, String> {
+    let store = HashCacheStore::in_dir(&data_dir()?);
+    let cache = Arc::new(store.load().unwrap_or_else(|e| {
+        log::warn!("Failed to load hash cache: {}", e);
+        Default::default()
+    }));
+    let result = scan(cache.clone());
+    if let Err(e) = store.save(&cache) {
+        log::warn!("Failed to save hash cache: {}", e);
+    }
+    Ok(result)
+}
+
+fn scan(target: ScanTarget, mut config: AppConfig, cwd: &Path, json: bool) -> Result<(), String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+
+    match target {
+        ScanTarget::Caches => {
+            let store = DirIndexStore::in_dir(&data_dir()?);
+            let index = Arc::new(store.load().unwrap_or_else(|e| {
+                log::warn!("Failed to load directory index: {}", e);
+                Default::default()
+            }));
+            let mut result = CacheScanner::for_current_user()
+                .ok_or("Could not determine home directory")?
+                .with_index(index.clone())
+                .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+                .scan();
+            if let Err(e) = store.save(&index) {
+                log::warn!("Failed to save directory index: {}", e);
+            }
+            ProtectionEngine::new(&home, config.user_profile).apply(&mut result);
+
+            if json {
+                return print_json(&result);
+            }
+            let mut rows = Vec::new();
+            for category in &result.categories {
+                for item in &category.items {
+                    rows.push(vec![
+                        category.name.clone(),
+                        format_bytes(item.size),
+                        format!("{:?}", item.safe_to_delete),
+                        item.path.display().to_string(),
+                    ]);
+                }
+            }
+            print_table(&["CATEGORY", "SIZE", "SAFETY", "PATH"], rows);
+            println!("Total: {}", format_bytes(result.total_size));
+        }
+        ScanTarget::Large {
+            threshold_mb,
+            paths,
+        } => {
+            if let Some(threshold_mb) = threshold_mb {
+                config.large_file_threshold_mb = threshold_mb;
+            }
+            if !paths.is_empty() {
+                config.scan_locations.custom_scan_paths = absolute(paths, cwd);
+            }
+            config.validate()?;
+            let result = LargeFileScanner::from_config(&config, &home).scan();
+
+            if json {
+                return print_json(&result);
+            }
+            print_table(
+                &["SIZE", "TYPE", "MODIFIED", "PATH"],
+                result
+                    .files
+                    .iter()
+                    .map(|f| {
+                        vec![
+                            format_bytes(f.size),
+                            format!("{:?}", f.media_type),
+                            format_relative_time(f.modified),
+                            f.path.display().to_string(),
+                        ]
+                    })
+                    .collect(),
+            );
+            println!("Total: {}", format_bytes(result.total_size));
+        }
+        ScanTarget::Duplicates {
+            images: true,
+            max_distance,
+            paths,
+            ..
+        } => {
+            let mut scanner = SimilarImageScanner::new(absolute(paths, cwd))
+                .with_exclusions(ExclusionMatcher::new(&config.exclusions));
+            if let Some(max_distance) = max_distance {
+                scanner = scanner.with_max_distance(max_distance);
+            }
+            let result = scanner.scan();
+
+            if json {
+                return print_json(&result);
+            }
+            let mut rows = Vec::new();
+            for group in &result.groups {
+                for image in &group.images {
+                    rows.push(vec![
+                        image.distance.to_string(),
+                        format!("{}x{}", image.width, image.height),
+                        if image.is_original {
+                            "original"
+                        } else {
+                            "copy"
+                        }
+                        .to_string(),
+                        image.path.display().to_string(),
+                    ]);
+                }
+            }
+            print_table(&["DISTANCE", "RESOLUTION", "ROLE", "PATH"], rows);
+            println!("Wasted: {}", format_bytes(result.total_wasted_space));
+        }
+        ScanTarget::Duplicates {
+            folders: true,
+            paths,
+            ..
+        } => {
+            let scanner = DuplicateFolderScanner::new(absolute(paths, cwd))
+                .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+                .with_hash_algorithm(config.hash_algorithm);
+            let result = with_hash_cache(|cache| scanner.with_hash_cache(cache).scan())?;
+
+            if json {
+                return print_json(&result);
+            }
+            let mut rows = Vec::new();
+            for group in &result.groups {
+                let matched = match &group.digest {
+                    Some(digest) => digest.chars().take(12).collect(),
+                    None => format!("{:.0}% shared", group.similarity * 100.0),
+                };
+                for folder in &group.folders {
+                    rows.push(vec![
+                        matched.clone(),
+                        format_bytes(folder.size),
+                        if folder.is_original {
+                            "original"
+                        } else {
+                            "copy"
+                        }
+                        .to_string(),
+                        folder.path.display().to_string(),
+                    ]);
+                }
+            }
+            print_table(&["MATCH", "SIZE", "ROLE", "PATH"], rows);
+            println!("Wasted: {}", format_bytes(result.total_wasted_space));
+        }
+        ScanTarget::Duplicates { paths, .. } => {
+            let scanner = DuplicateScanner::new(absolute(paths, cwd))
+                .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+                .with_hash_algorithm(config.hash_algorithm);
+            let result = with_hash_cache(|cache| scanner.with_hash_cache(cache).scan())?;
+
+            if json {
+                return print_json(&result);
+            }
+            let mut rows = Vec::new();
+            for group in &result.groups {
+                for file in &group.files {
+                    rows.push(vec![
+                        group.hash.chars().take(12).collect(),
+                        format_bytes(group.size),
+                        if file.is_original { "original" } else { "copy" }.to_string(),
+                        file.path.display().to_string(),
+                    ]);
+                }
+            }
+            print_table(&["HASH", "SIZE", "ROLE", "PATH"], rows);
+            println!("Wasted: {}", format_bytes(result.total_wasted_space));
+        }
+        ScanTarget::Orphans => {
+            let result = OrphanScanner::for_current_user()
+                .ok_or("Could not determine home directory")?
+                .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+                .scan();
+
+            if json {
+                return print_json(&result);
+            }
+            let mut rows = Vec::new();
+            for app in &result.orphaned_apps {
+                for file in &app.files {
+                    rows.push(vec![
+                        app.presumed_name.clone(),
+                        format_bytes(file.size),
+                        format!("{:?}", file.file_type),
+                        file.path.display().to_string(),
+                    ]);
+                }
+            }
+            print_table(&["APP", "SIZE", "TYPE", "PATH"], rows);
+            println!("Total: {}", format_bytes(result.total_size));
+        }
+    }
+    Ok(())
+}
+
+fn build_cleaner(config: AppConfig, override_protection: bool) -> Result<Cleaner, String> {
+    let protection = ProtectionEngine::for_current_user(config.user_profile)
+        .ok_or("Could not determine home directory")?;
+    Ok(Cleaner::for_current_user()
+        .ok_or("Could not determine home directory")?
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+        .with_protection(protection)
+        .override_protection(override_protection))
+}
+
+/// Resolve relative arguments against the working directory
+fn absolute(paths: Vec<PathBuf>, cwd: &Path) -> Vec<PathBuf> {
+    paths.into_iter().map(|p| cwd.join(p)).collect()
+}
+
+/// The settings the app would apply, so the CLI scans and cleans the same way
+fn load_config() -> Result<AppConfig, String> {
+    let dir = app_config_dir().ok_or("Could not determine config directory")?;
+    ConfigStore::in_dir(&dir)
+        .load_validated()
+        .map_err(|e| e.to_string())
+}
+
+fn data_dir() -> Result<PathBuf, String> {
+    app_data_dir().ok_or_else(|| "Could not determine data directory".to_string())
+}
+
+fn print_plan(plan: &CleanPlan) {
+    print_table(
+        &["ACTION", "SIZE", "SAFETY", "RULE", "PATH"],
+        plan.items
+            .iter()
+            .map(|item| {
+                vec![
+                    if item.allowed { "clean" } else { "skip" }.to_string(),
+                    format_bytes(item.size),
+                    format!("{:?}", item.safety),
+                    item.rule.clone(),
+                    item.path.display().to_string(),
+                ]
+            })
+            .collect(),
+    );
+    println!("Would reclaim {}", format_bytes(plan.total_size));
+}
+
+fn print_clean_result(result: &CleaningResult) {
+    print_table(
+        &["STATUS", "SIZE", "CATEGORY", "PATH"],
+        result
+            .items
+            .iter()
+            .map(|item| {
+                let status = match &item.error {
+                    Some(error) => format!("skipped: {}", error),
+                    None => "cleaned".to_string(),
+                };
+                vec![
+                    status,
+                    format_bytes(item.size),
+                    item.category.clone(),
+                    item.path.display().to_string(),
+                ]
+            })
+            .collect(),
+    );
+    println!(
+        "Reclaimed {} from {} items",
+        format_bytes(result.space_reclaimed),
+        result.items_cleaned
+    );
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// Print rows under a header with every column padded to its widest cell
+///
+/// The last column is left unpadded since it usually holds a path.
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let last = row.len().saturating_sub(1);
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i == last {
+                    cell.clone()
+                } else {
+                    format!("{:<width$}", cell, width = widths[i])
+                }
+            })
+            .collect();
+        println!("{}", line.join("  "));
+    }
+}
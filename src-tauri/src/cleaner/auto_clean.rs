@@ -0,0 +1,509 @@
+use crate::analyzer::protection::ProtectionEngine;
+use crate::cleaner::history::HistoryStore;
+use crate::cleaner::safe_delete::{history_entry, Cleaner};
+use crate::models::config::{AppConfig, AutoCleanSchedule};
+use crate::models::history::{CleanMode, CleaningResult};
+use crate::models::scan_result::SafetyLevel;
+use crate::scanner::cache_scanner::CacheScanner;
+use crate::scanner::now_timestamp;
+use crate::utils::config_store::ConfigStore;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::write_atomic;
+use chrono::{Months, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+pub const AUTO_CLEAN_STATE_FILE_NAME: &str = "auto_clean.json";
+
+/// How often the scheduler wakes to check the schedule and the free space
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Minimum time between low disk space cleanings, so a disk that stays full
+/// after cleaning isn't rescanned on every wake
+const LOW_SPACE_COOLDOWN_SECS: i64 = 60 * 60;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const BYTES_PER_GB: u64 = 1024 * 1024 * 1024;
+
+/// Source of the current time as a Unix timestamp
+pub trait Clock: Send + Sync {
+    fn now(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        now_timestamp()
+    }
+}
+
+/// Source of the free space on the startup disk
+pub trait DiskProbe: Send + Sync {
+    fn available_space(&self) -> Result<u64, String>;
+}
+
+/// Free space as reported by `get_disk_info`
+pub struct SystemDiskProbe;
+
+impl DiskProbe for SystemDiskProbe {
+    fn available_space(&self) -> Result<u64, String> {
+        crate::commands::system::get_disk_info()
+            .map(|info| info.available_space)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutoCleanState {
+    /// When the scheduler last cleaned, as a Unix timestamp
+    pub last_run: Option<i64>,
+    /// When auto-clean was found enabled, so timed schedules count from then
+    /// rather than firing as soon as it is switched on
+    pub enabled_since: Option<i64>,
+}
+
+/// JSON file remembering when the scheduler last ran
+pub struct AutoCleanStateStore {
+    path: PathBuf,
+}
+
+impl AutoCleanStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store kept in the given app data directory
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::new(dir.join(AUTO_CLEAN_STATE_FILE_NAME))
+    }
+
+    /// Load the state, treating a missing file as never run
+    pub fn load(&self) -> io::Result<AutoCleanState> {
+        match fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(AutoCleanState::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, state: &AutoCleanState) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec_pretty(state)?)
+    }
+}
+
+/// Whether `schedule` calls for a cleaning at `now`
+///
+/// `available_space` is only asked for by the low disk space schedule.
+pub fn is_due(
+    schedule: &AutoCleanSchedule,
+    last_run: Option<i64>,
+    now: i64,
+    available_space: impl FnOnce() -> Option<u64>,
+) -> bool {
+    if *schedule == AutoCleanSchedule::OnDemand {
+        return false;
+    }
+    let next_run = |last: i64| match schedule {
+        AutoCleanSchedule::Daily => Some(last + SECONDS_PER_DAY),
+        AutoCleanSchedule::Weekly => Some(last + 7 * SECONDS_PER_DAY),
+        AutoCleanSchedule::Monthly => Utc
+            .timestamp_opt(last, 0)
+            .single()
+            .and_then(|t| t.checked_add_months(Months::new(1)))
+            .map(|t| t.timestamp()),
+        AutoCleanSchedule::OnLowDiskSpace { .. } => Some(last + LOW_SPACE_COOLDOWN_SECS),
+        AutoCleanSchedule::OnDemand => None,
+    };
+    if let Some(last) = last_run {
+        match next_run(last) {
+            Some(next) if now >= next => {}
+            _ => return false,
+        }
+    }
+    match schedule {
+        AutoCleanSchedule::OnLowDiskSpace { threshold_gb } => available_space()
+            .is_some_and(|available| available < u64::from(*threshold_gb) * BYTES_PER_GB),
+        _ => true,
+    }
+}
+
+/// Cleans the configured cache categories when `AutoCleanConfig` says so
+///
+/// Only items the scanner rates safe and that haven't changed for
+/// `min_age_days` are removed. The settings are reread on every check so
+/// changes apply without a restart.
+pub struct AutoCleaner {
+    home: PathBuf,
+    temp_dirs: Vec<PathBuf>,
+    config: ConfigStore,
+    history: HistoryStore,
+    state: AutoCleanStateStore,
+    mode: CleanMode,
+    clock: Arc<dyn Clock>,
+    disk: Arc<dyn DiskProbe>,
+}
+
+impl AutoCleaner {
+    /// Create a cleaner for `home` using the settings in `config_dir` and
+    /// keeping its history and state in `data_dir`
+    pub fn new(home: impl Into<PathBuf>, config_dir: &Path, data_dir: &Path) -> Self {
+        Self {
+            home: home.into(),
+            temp_dirs: Vec::new(),
+            config: ConfigStore::in_dir(config_dir),
+            history: HistoryStore::in_dir(data_dir),
+            state: AutoCleanStateStore::in_dir(data_dir),
+            mode: CleanMode::Trash,
+            clock: Arc::new(SystemClock),
+            disk: Arc::new(SystemDiskProbe),
+        }
+    }
+
+    /// Create a cleaner for the current user, including the system temp directory
+    pub fn for_current_user(config_dir: &Path, data_dir: &Path) -> Option<Self> {
+        let home = dirs::home_dir()?;
+        Some(Self::new(home, config_dir, data_dir).with_temp_dirs(vec![std::env::temp_dir()]))
+    }
+
+    pub fn with_temp_dirs(mut self, temp_dirs: Vec<PathBuf>) -> Self {
+        self.temp_dirs = temp_dirs;
+        self
+    }
+
+    /// Move items to the Trash (the default) or delete them
+    pub fn with_mode(mut self, mode: CleanMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_disk_probe(mut self, disk: Arc<dyn DiskProbe>) -> Self {
+        self.disk = disk;
+        self
+    }
+
+    /// Clean if the schedule calls for it, returning what was cleaned
+    pub fn run_if_due(&self) -> Result<Option<CleaningResult>, String> {
+        let config = self.config.load().map_err(|e| e.to_string())?;
+        config.validate()?;
+        let mut state = self.state.load().unwrap_or_else(|e| {
+            log::warn!("Failed to load auto-clean state: {}", e);
+            AutoCleanState::default()
+        });
+        let now = self.clock.now();
+
+        if !config.auto_clean.enabled {
+            if state.enabled_since.take().is_some() {
+                self.state.save(&state).map_err(|e| e.to_string())?;
+            }
+            return Ok(None);
+        }
+        if state.enabled_since.is_none() {
+            state.enabled_since = Some(now);
+            self.state.save(&state).map_err(|e| e.to_string())?;
+        }
+
+        // Low disk space is checked at once; the last run only spaces cleanings out
+        let since = match config.auto_clean.schedule {
+            AutoCleanSchedule::OnLowDiskSpace { .. } => state.last_run,
+            _ => state.last_run.max(state.enabled_since),
+        };
+        let due = is_due(&config.auto_clean.schedule, since, now, || {
+            self.disk
+                .available_space()
+                .map_err(|e| log::warn!("Failed to read free disk space: {}", e))
+                .ok()
+        });
+        if !due {
+            return Ok(None);
+        }
+
+        let result = self.clean(&config, now);
+        if let Some(mut entry) = history_entry(&result) {
+            entry.timestamp = now;
+            entry.automatic = true;
+            if let Err(e) = self.history.append(entry) {
+                log::warn!("Failed to record cleaning history: {}", e);
+            }
+        }
+
+        state.last_run = Some(now);
+        self.state.save(&state).map_err(|e| e.to_string())?;
+        Ok(Some(result))
+    }
+
+    fn clean(&self, config: &AppConfig, now: i64) -> CleaningResult {
+        let exclusions = ExclusionMatcher::new(&config.exclusions);
+        let protection = ProtectionEngine::new(&self.home, config.user_profile.clone());
+
+        let mut scan = CacheScanner::new(&self.home)
+            .with_temp_dirs(self.temp_dirs.clone())
+            .with_exclusions(exclusions.clone())
+            .scan();
+        protection.apply(&mut scan);
+
+        let min_age_days = i64::from(config.auto_clean.min_age_days);
+        let paths: Vec<PathBuf> = scan
+            .categories
+            .into_iter()
+            .filter(|c| !c.is_protected)
+            .filter(|c| config.auto_clean.categories.contains(&c.category_type))
+            .flat_map(|c| c.items)
+            .filter(|item| item.safe_to_delete == SafetyLevel::Safe)
+            .filter(|item| age_days(&item.path, now).is_some_and(|age| age >= min_age_days))
+            .map(|item| item.path)
+            .collect();
+
+        Cleaner::new(&self.home)
+            .with_exclusions(exclusions)
+            .with_protection(protection)
+            .clean(&paths, self.mode)
+    }
+}
+
+/// Whole days between the last change to `path` or anything inside it and `now`
+///
+/// A directory's own mtime only moves when direct children come and go, so
+/// a cache rewritten deep inside would otherwise look old.
+fn age_days(path: &Path, now: i64) -> Option<i64> {
+    let modified = WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .max()?;
+    let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some((now - modified) / SECONDS_PER_DAY)
+}
+
+/// Check the schedule on a background thread every `POLL_INTERVAL`
+pub fn spawn_scheduler(cleaner: AutoCleaner) -> io::Result<()> {
+    std::thread::Builder::new()
+        .name("auto-clean".to_string())
+        .spawn(move || loop {
+            match cleaner.run_if_due() {
+                Ok(Some(result)) => log::info!(
+                    "Auto-clean removed {} items, {} bytes",
+                    result.items_cleaned,
+                    result.space_reclaimed
+                ),
+                Ok(None) => {}
+                Err(e) => log::warn!("Auto-clean failed: {}", e),
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        })
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::AutoCleanConfig;
+    use crate::models::history::HistoryQuery;
+    use crate::models::scan_result::CacheCategoryType;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use tempfile::tempdir;
+
+    // 2024-01-15 12:00 UTC
+    const NOW: i64 = 1_705_320_000;
+
+    struct FixedClock(AtomicI64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    struct FixedDisk(u64);
+
+    impl DiskProbe for FixedDisk {
+        fn available_space(&self) -> Result<u64, String> {
+            Ok(self.0)
+        }
+    }
+
+    fn backdate(path: &Path, days: i64) {
+        let time = UNIX_EPOCH + Duration::from_secs((NOW - days * SECONDS_PER_DAY) as u64);
+        fs::File::open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    fn save_config(dir: &Path, schedule: AutoCleanSchedule, categories: &[CacheCategoryType]) {
+        let config = AppConfig {
+            auto_clean: AutoCleanConfig {
+                enabled: true,
+                schedule,
+                categories: categories.to_vec(),
+                min_age_days: 30,
+            },
+            ..AppConfig::default()
+        };
+        ConfigStore::in_dir(dir).save(&config).unwrap();
+    }
+
+    #[test]
+    fn test_is_due() {
+        let never = || -> Option<u64> { panic!("disk probed for a timed schedule") };
+        assert!(is_due(&AutoCleanSchedule::Daily, None, NOW, never));
+        assert!(!is_due(
+            &AutoCleanSchedule::Daily,
+            Some(NOW - 3600),
+            NOW,
+            never
+        ));
+        assert!(is_due(
+            &AutoCleanSchedule::Weekly,
+            Some(NOW - 7 * SECONDS_PER_DAY),
+            NOW,
+            never
+        ));
+        // 2023-12-15 is one calendar month, but only 31 days, before NOW
+        let dec_15 = NOW - 31 * SECONDS_PER_DAY;
+        assert!(is_due(
+            &AutoCleanSchedule::Monthly,
+            Some(dec_15),
+            NOW,
+            never
+        ));
+        assert!(!is_due(
+            &AutoCleanSchedule::Monthly,
+            Some(dec_15 + 3600),
+            NOW,
+            never
+        ));
+        assert!(!is_due(&AutoCleanSchedule::OnDemand, None, NOW, never));
+
+        let low = AutoCleanSchedule::OnLowDiskSpace { threshold_gb: 10 };
+        assert!(is_due(&low, None, NOW, || Some(5 * BYTES_PER_GB)));
+        assert!(!is_due(&low, None, NOW, || Some(20 * BYTES_PER_GB)));
+        assert!(!is_due(&low, None, NOW, || None));
+        assert!(!is_due(&low, Some(NOW - 60), NOW, || Some(0)));
+    }
+
+    #[test]
+    fn test_cleans_old_items_in_configured_categories() {
+        let dir = tempdir().unwrap();
+        let home = dir.path().join("home");
+        let caches = home.join("Library/Caches");
+        let logs = home.join("Library/Logs");
+        for (path, days, blob_days) in [
+            (caches.join("com.example.Old"), 60, 60),
+            (caches.join("com.example.Fresh"), 2, 2),
+            // Rewritten inside every day, which leaves the folder's own mtime alone
+            (caches.join("com.example.Busy"), 60, 1),
+            (logs.join("OldApp"), 60, 60),
+        ] {
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("blob"), vec![0u8; 64]).unwrap();
+            backdate(&path.join("blob"), blob_days);
+            backdate(&path, days);
+        }
+        save_config(
+            dir.path(),
+            AutoCleanSchedule::Daily,
+            &[CacheCategoryType::Application],
+        );
+
+        let clock = Arc::new(FixedClock(AtomicI64::new(NOW - SECONDS_PER_DAY)));
+        let auto = AutoCleaner::new(&home, dir.path(), dir.path())
+            .with_mode(CleanMode::Permanent)
+            .with_clock(clock.clone());
+
+        // The first check after enabling only starts the schedule
+        assert!(auto.run_if_due().unwrap().is_none());
+        clock.0.store(NOW, Ordering::SeqCst);
+        let result = auto.run_if_due().unwrap().unwrap();
+        assert_eq!(result.items_cleaned, 1);
+        assert!(!caches.join("com.example.Old").exists());
+        assert!(caches.join("com.example.Fresh").exists());
+        assert!(caches.join("com.example.Busy").exists());
+        assert!(logs.join("OldApp").exists());
+
+        let history = HistoryStore::in_dir(dir.path())
+            .list(&HistoryQuery::default())
+            .unwrap();
+        assert_eq!(history.total, 1);
+        assert!(history.entries[0].automatic);
+        assert_eq!(history.entries[0].timestamp, NOW);
+        assert_eq!(
+            AutoCleanStateStore::in_dir(dir.path())
+                .load()
+                .unwrap()
+                .last_run,
+            Some(NOW)
+        );
+
+        // Not due again until a day has passed
+        assert!(auto.run_if_due().unwrap().is_none());
+        clock.0.store(NOW + SECONDS_PER_DAY, Ordering::SeqCst);
+        assert!(auto.run_if_due().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_schedule_restarts_when_reenabled() {
+        let dir = tempdir().unwrap();
+        let state = AutoCleanStateStore::in_dir(dir.path());
+        save_config(dir.path(), AutoCleanSchedule::Weekly, &[]);
+        let clock = Arc::new(FixedClock(AtomicI64::new(NOW)));
+        let auto = AutoCleaner::new(dir.path().join("home"), dir.path(), dir.path())
+            .with_clock(clock.clone());
+
+        assert!(auto.run_if_due().unwrap().is_none());
+        assert_eq!(state.load().unwrap().enabled_since, Some(NOW));
+        clock.0.store(NOW + 7 * SECONDS_PER_DAY, Ordering::SeqCst);
+        assert!(auto.run_if_due().unwrap().is_some());
+
+        ConfigStore::in_dir(dir.path())
+            .save(&AppConfig::default())
+            .unwrap();
+        assert!(auto.run_if_due().unwrap().is_none());
+        assert_eq!(state.load().unwrap().enabled_since, None);
+
+        // Switched back on long after the last run: a full week from now
+        clock.0.store(NOW + 60 * SECONDS_PER_DAY, Ordering::SeqCst);
+        save_config(dir.path(), AutoCleanSchedule::Weekly, &[]);
+        assert!(auto.run_if_due().unwrap().is_none());
+        clock.0.store(NOW + 67 * SECONDS_PER_DAY, Ordering::SeqCst);
+        assert!(auto.run_if_due().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_low_disk_space_uses_probe() {
+        let dir = tempdir().unwrap();
+        save_config(
+            dir.path(),
+            AutoCleanSchedule::OnLowDiskSpace { threshold_gb: 10 },
+            &[CacheCategoryType::Logs],
+        );
+        let auto = |available| {
+            AutoCleaner::new(dir.path().join("home"), dir.path(), dir.path())
+                .with_clock(Arc::new(FixedClock(AtomicI64::new(NOW))))
+                .with_disk_probe(Arc::new(FixedDisk(available)))
+        };
+
+        assert!(auto(50 * BYTES_PER_GB).run_if_due().unwrap().is_none());
+        assert!(auto(BYTES_PER_GB).run_if_due().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_disabled_does_nothing() {
+        let dir = tempdir().unwrap();
+        let auto = AutoCleaner::new(dir.path(), dir.path(), dir.path());
+        assert!(auto.run_if_due().unwrap().is_none());
+        assert!(AutoCleanStateStore::in_dir(dir.path())
+            .load()
+            .unwrap()
+            .last_run
+            .is_none());
+    }
+}
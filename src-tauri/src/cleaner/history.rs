@@ -0,0 +1,364 @@
+use crate::models::history::{
+    CategoryReclaimed, CleaningEntry, CleaningHistory, HistoryPage, HistoryQuery, HistorySummary,
+    MonthlyReclaimed,
+};
+use crate::utils::fs::write_atomic;
+use chrono::{Datelike, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const HISTORY_FILE_NAME: &str = "history.json";
+
+/// Version of the on-disk format written by this build
+pub const HISTORY_SCHEMA_VERSION: u32 = 1;
+
+/// Serializes read-modify-write cycles so concurrent cleans don't drop entries
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// On-disk envelope; files written before versioning have no `version` and load as 0
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+    #[serde(default)]
+    version: u32,
+    #[serde(flatten)]
+    history: CleaningHistory,
+}
+
+/// JSON file holding the cleaning history
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store kept in the given app data directory
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::new(dir.join(HISTORY_FILE_NAME))
+    }
+
+    /// Load the history, treating a missing file as empty
+    ///
+    /// Files from a newer schema are rejected rather than silently rewritten.
+    pub fn load(&self) -> io::Result<CleaningHistory> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(CleaningHistory { entries: vec![] })
+            }
+            Err(e) => return Err(e),
+        };
+
+        let file: HistoryFile = serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if file.version > HISTORY_SCHEMA_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "History file version {} is newer than supported version {}",
+                    file.version, HISTORY_SCHEMA_VERSION
+                ),
+            ));
+        }
+        Ok(file.history)
+    }
+
+    fn save(&self, history: CleaningHistory) -> io::Result<()> {
+        let file = HistoryFile {
+            version: HISTORY_SCHEMA_VERSION,
+            history,
+        };
+        let json = serde_json::to_vec_pretty(&file)?;
+        write_atomic(&self.path, &json)
+    }
+
+    pub fn append(&self, entry: CleaningEntry) -> io::Result<()> {
+        self.update(|history| history.entries.push(entry))
+    }
+
+    /// Load, modify and save the history while holding the store lock
+    pub fn update<R>(&self, f: impl FnOnce(&mut CleaningHistory) -> R) -> io::Result<R> {
+        let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut history = self.load()?;
+        let result = f(&mut history);
+        self.save(history)?;
+        Ok(result)
+    }
+
+    /// Entries matching `query`, newest first
+    pub fn list(&self, query: &HistoryQuery) -> io::Result<HistoryPage> {
+        let mut matching: Vec<CleaningEntry> = self
+            .load()?
+            .entries
+            .into_iter()
+            .filter(|e| matches_query(e, query))
+            .collect();
+        matching.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+
+        let total = matching.len();
+        let entries = matching
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(HistoryPage {
+            entries,
+            total,
+            offset: query.offset,
+        })
+    }
+
+    /// Totals per calendar month (in `tz`) and per category for matching entries
+    ///
+    /// Pagination fields of the query are ignored.
+    pub fn summary<Tz: TimeZone>(
+        &self,
+        query: &HistoryQuery,
+        tz: &Tz,
+    ) -> io::Result<HistorySummary> {
+        let entries: Vec<CleaningEntry> = self
+            .load()?
+            .entries
+            .into_iter()
+            .filter(|e| matches_query(e, query))
+            .collect();
+        Ok(summarize(&entries, query.category.as_deref(), tz))
+    }
+}
+
+fn matches_query(entry: &CleaningEntry, query: &HistoryQuery) -> bool {
+    query.from.map_or(true, |from| entry.timestamp >= from)
+        && query.to.map_or(true, |to| entry.timestamp <= to)
+        && query
+            .category
+            .as_ref()
+            .map_or(true, |c| entry.categories.contains(c))
+}
+
+fn summarize<Tz: TimeZone>(
+    entries: &[CleaningEntry],
+    category: Option<&str>,
+    tz: &Tz,
+) -> HistorySummary {
+    let mut by_month: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    let mut by_category: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+
+    for entry in entries {
+        // With a category filter only that category's items count towards the totals
+        let items = entry
+            .items
+            .iter()
+            .filter(|i| category.map_or(true, |c| i.category == c));
+
+        let month = tz
+            .timestamp_opt(entry.timestamp, 0)
+            .single()
+            .map(|t| format!("{:04}-{:02}", t.year(), t.month()))
+            .unwrap_or_default();
+        for item in items {
+            let monthly = by_month.entry(month.clone()).or_default();
+            monthly.0 += item.size;
+            monthly.1 += 1;
+
+            let per_category = by_category.entry(item.category.clone()).or_default();
+            per_category.0 += item.size;
+            per_category.1 += 1;
+        }
+    }
+
+    let by_category: Vec<CategoryReclaimed> = by_category
+        .into_iter()
+        .map(
+            |(category, (space_reclaimed, items_cleaned))| CategoryReclaimed {
+                category,
+                space_reclaimed,
+                items_cleaned,
+            },
+        )
+        .collect();
+
+    HistorySummary {
+        total_reclaimed: by_category.iter().map(|c| c.space_reclaimed).sum(),
+        total_items: by_category.iter().map(|c| c.items_cleaned).sum(),
+        by_month: by_month
+            .into_iter()
+            .map(
+                |(month, (space_reclaimed, items_cleaned))| MonthlyReclaimed {
+                    month,
+                    space_reclaimed,
+                    items_cleaned,
+                },
+            )
+            .collect(),
+        by_category,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::history::CleanedItem;
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    // 2024-01-15 and 2024-02-10, 12:00 UTC
+    const JAN: i64 = 1_705_320_000;
+    const FEB: i64 = 1_707_566_400;
+
+    fn entry(timestamp: i64, items: &[(&str, u64)]) -> CleaningEntry {
+        let items: Vec<CleanedItem> = items
+            .iter()
+            .map(|(category, size)| CleanedItem {
+                path: PathBuf::from(format!("/tmp/{}", category)),
+                size: *size,
+                category: category.to_string(),
+            })
+            .collect();
+        let mut categories: Vec<String> = items.iter().map(|i| i.category.clone()).collect();
+        categories.dedup();
+        CleaningEntry {
+            id: timestamp.to_string(),
+            timestamp,
+            space_reclaimed: items.iter().map(|i| i.size).sum(),
+            items_cleaned: items.len() as u32,
+            categories,
+            items,
+            restore_journal: vec![],
+            automatic: false,
+        }
+    }
+
+    fn seeded_store(dir: &Path) -> HistoryStore {
+        let store = HistoryStore::in_dir(dir);
+        store
+            .append(entry(JAN, &[("Logs", 10), ("Browser", 100)]))
+            .unwrap();
+        store.append(entry(JAN + 60, &[("Logs", 5)])).unwrap();
+        store.append(entry(FEB, &[("Developer", 1000)])).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::in_dir(dir.path());
+        assert!(store.load().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_append_writes_versioned_file() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::in_dir(&dir.path().join("nested"));
+        store.append(entry(1, &[("Logs", 1)])).unwrap();
+        store.append(entry(2, &[("Logs", 1)])).unwrap();
+
+        let raw: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.path().join("nested/history.json")).unwrap())
+                .unwrap();
+        assert_eq!(raw["version"], HISTORY_SCHEMA_VERSION);
+        assert_eq!(raw["entries"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_loads_unversioned_and_rejects_newer_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE_NAME);
+        let legacy = CleaningHistory {
+            entries: vec![entry(1, &[("Logs", 1)])],
+        };
+        fs::write(&path, serde_json::to_vec(&legacy).unwrap()).unwrap();
+        let store = HistoryStore::new(&path);
+        assert_eq!(store.load().unwrap().entries.len(), 1);
+
+        fs::write(&path, br#"{"version": 99, "entries": []}"#).unwrap();
+        assert!(store.load().is_err());
+        // A failed load must not clobber the newer file
+        assert!(store.append(entry(2, &[("Logs", 1)])).is_err());
+        assert!(fs::read_to_string(&path).unwrap().contains("99"));
+    }
+
+    #[test]
+    fn test_list_filters_and_paginates() {
+        let dir = tempdir().unwrap();
+        let store = seeded_store(dir.path());
+
+        let page = store
+            .list(&HistoryQuery {
+                limit: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[0].timestamp, FEB);
+
+        let page = store
+            .list(&HistoryQuery {
+                offset: 2,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].timestamp, JAN);
+
+        let logs_in_jan = store
+            .list(&HistoryQuery {
+                from: Some(JAN),
+                to: Some(FEB - 1),
+                category: Some("Logs".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(logs_in_jan.total, 2);
+    }
+
+    #[test]
+    fn test_summary_by_month_and_category() {
+        let dir = tempdir().unwrap();
+        let store = seeded_store(dir.path());
+
+        let summary = store.summary(&HistoryQuery::default(), &Utc).unwrap();
+        assert_eq!(summary.total_reclaimed, 1115);
+        assert_eq!(summary.total_items, 4);
+        assert_eq!(
+            summary.by_month,
+            vec![
+                MonthlyReclaimed {
+                    month: "2024-01".to_string(),
+                    space_reclaimed: 115,
+                    items_cleaned: 3,
+                },
+                MonthlyReclaimed {
+                    month: "2024-02".to_string(),
+                    space_reclaimed: 1000,
+                    items_cleaned: 1,
+                },
+            ]
+        );
+        let logs = summary
+            .by_category
+            .iter()
+            .find(|c| c.category == "Logs")
+            .unwrap();
+        assert_eq!(logs.space_reclaimed, 15);
+
+        let logs_only = store
+            .summary(
+                &HistoryQuery {
+                    category: Some("Logs".to_string()),
+                    ..Default::default()
+                },
+                &Utc,
+            )
+            .unwrap();
+        assert_eq!(logs_only.total_reclaimed, 15);
+        assert_eq!(logs_only.by_category.len(), 1);
+    }
+}
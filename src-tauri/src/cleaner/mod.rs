@@ -0,0 +1,5 @@
+pub mod auto_clean;
+pub mod history;
+pub mod plan;
+pub mod restore;
+pub mod safe_delete;
@@ -0,0 +1,73 @@
+use crate::cleaner::safe_delete::Cleaner;
+use crate::error::CleanMacError;
+use crate::models::history::CleanPlan;
+use crate::scanner::now_timestamp;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Plans older than this must be made again before they can be carried out
+pub const PLAN_TTL_SECS: i64 = 600;
+
+/// Plans waiting for the user to confirm them, with the cleaner that made them
+/// so they are carried out under the same rules they were shown with
+#[derive(Default)]
+pub struct PlanStore {
+    plans: Mutex<HashMap<String, (CleanPlan, Cleaner)>>,
+}
+
+impl PlanStore {
+    pub fn insert(&self, plan: CleanPlan, cleaner: Cleaner) {
+        let mut plans = self.lock();
+        let now = now_timestamp();
+        plans.retain(|_, (plan, _)| !is_expired(plan, now));
+        plans.insert(plan.id.clone(), (plan, cleaner));
+    }
+
+    /// Remove a plan so it can be carried out exactly once
+    pub fn take(&self, plan_id: &str) -> Result<(CleanPlan, Cleaner), CleanMacError> {
+        let (plan, cleaner) = self.lock().remove(plan_id).ok_or_else(|| {
+            CleanMacError::not_found(format!("No pending clean plan with id {}", plan_id))
+        })?;
+        if is_expired(&plan, now_timestamp()) {
+            return Err(CleanMacError::not_found(
+                "The clean plan has expired, please review it again",
+            ));
+        }
+        Ok((plan, cleaner))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (CleanPlan, Cleaner)>> {
+        self.plans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn is_expired(plan: &CleanPlan, now: i64) -> bool {
+    now - plan.created_at > PLAN_TTL_SECS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::history::CleanMode;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_plans_are_taken_once_and_expire() {
+        let home = tempdir().unwrap();
+        let cleaner = || Cleaner::new(home.path());
+        let store = PlanStore::default();
+
+        let plan = cleaner().plan(&[], CleanMode::Trash);
+        let id = plan.id.clone();
+        store.insert(plan, cleaner());
+        assert!(store.take(&id).is_ok());
+        assert!(store.take(&id).is_err());
+
+        let mut stale = cleaner().plan(&[], CleanMode::Trash);
+        stale.created_at -= PLAN_TTL_SECS + 1;
+        let id = stale.id.clone();
+        store.insert(stale, cleaner());
+        let error = store.take(&id).err().unwrap();
+        assert!(error.to_string().contains("expired"));
+    }
+}
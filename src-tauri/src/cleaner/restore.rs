@@ -0,0 +1,389 @@
+use crate::cleaner::history::HistoryStore;
+use crate::error::CleanMacError;
+use crate::models::history::{RestoreItemOutcome, RestoreRecord, RestoreResult, RestoreStatus};
+use crate::utils::fs::rename_no_replace;
+use std::collections::HashMap;
+#[cfg(any(target_os = "macos", test))]
+use std::collections::HashSet;
+#[cfg(any(target_os = "macos", test))]
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Move `path` to the Trash, returning where it went when that is certain
+///
+/// Finder renames an item whose name is already taken in the Trash, so the
+/// Trash it lands in is listed before and after to see what was added.
+#[cfg(target_os = "macos")]
+pub fn move_to_trash(path: &Path, home: &Path) -> Result<Option<PathBuf>, trash::Error> {
+    let trash_dir = trash_dir_for(path, home);
+    let before = trash_dir.as_deref().map(list_names).unwrap_or_default();
+    trash::delete(path)?;
+
+    Ok(trash_dir.and_then(|dir| {
+        let name = added_entry(&before, list_names(&dir), path.file_name()?)?;
+        Some(dir.join(name))
+    }))
+}
+
+/// The entry that appeared in a listing, given the name of the item moved there
+///
+/// Were several added at once, only one that kept the item's name is certain.
+#[cfg(any(target_os = "macos", test))]
+fn added_entry(
+    before: &HashSet<OsString>,
+    after: HashSet<OsString>,
+    name: &OsStr,
+) -> Option<OsString> {
+    let mut added: Vec<OsString> = after
+        .into_iter()
+        .filter(|entry| !before.contains(entry))
+        .collect();
+    if added.len() == 1 {
+        return added.pop();
+    }
+    added.into_iter().find(|entry| entry == name)
+}
+
+/// Move `path` to the Trash; where it went is found afterwards by [`locate_trashed`]
+#[cfg(not(target_os = "macos"))]
+pub fn move_to_trash(path: &Path, _home: &Path) -> Result<Option<PathBuf>, trash::Error> {
+    trash::delete(path).map(|_| None)
+}
+
+/// The Trash `path` moves to: the home one for the home volume, otherwise the
+/// user's folder in `.Trashes` at the root of the volume
+#[cfg(target_os = "macos")]
+fn trash_dir_for(path: &Path, home: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = fs::symlink_metadata(path).ok()?.dev();
+    let home_metadata = fs::metadata(home).ok()?;
+    if home_metadata.dev() == device {
+        return Some(home.join(".Trash"));
+    }
+    let mut root = path;
+    while let Some(parent) = root.parent() {
+        if fs::metadata(parent).map_or(true, |m| m.dev() != device) {
+            break;
+        }
+        root = parent;
+    }
+    Some(root.join(".Trashes").join(home_metadata.uid().to_string()))
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn list_names(dir: &Path) -> HashSet<OsString> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Where each of `paths` ended up after being moved to the Trash
+///
+/// Paths whose location can't be determined are left out and can't be restored.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn locate_trashed(paths: &[PathBuf]) -> HashMap<PathBuf, PathBuf> {
+    let items = match trash::os_limited::list() {
+        Ok(items) => items,
+        Err(e) => {
+            log::warn!("Failed to list Trash contents: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    // The same path may have been trashed before, so keep the newest match
+    let mut newest: HashMap<PathBuf, (i64, PathBuf)> = HashMap::new();
+    for item in items {
+        let original = item.original_path();
+        if !paths.contains(&original) {
+            continue;
+        }
+        let Some(trashed) = trashed_file_for_info(Path::new(&item.id)) else {
+            continue;
+        };
+        match newest.get(&original) {
+            Some((time, _)) if *time >= item.time_deleted => {}
+            _ => {
+                newest.insert(original, (item.time_deleted, trashed));
+            }
+        }
+    }
+
+    newest
+        .into_iter()
+        .map(|(original, (_, trashed))| (original, trashed))
+        .collect()
+}
+
+/// Where each of `paths` ended up after being moved to the Trash
+///
+/// Nothing can be listed here; on macOS [`move_to_trash`] records it instead.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn locate_trashed(_paths: &[PathBuf]) -> HashMap<PathBuf, PathBuf> {
+    HashMap::new()
+}
+
+/// Freedesktop keeps `info/NAME.trashinfo` next to `files/NAME`
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trashed_file_for_info(info: &Path) -> Option<PathBuf> {
+    let trash_dir = info.parent()?.parent()?;
+    Some(trash_dir.join("files").join(info.file_stem()?))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn info_for_trashed_file(trashed: &Path) -> Option<PathBuf> {
+    let trash_dir = trashed.parent()?.parent()?;
+    let mut name = trashed.file_name()?.to_os_string();
+    name.push(".trashinfo");
+    Some(trash_dir.join("info").join(name))
+}
+
+/// Restore every journaled item of a history entry
+///
+/// Restored items and items gone from the Trash leave the journal; conflicts
+/// and failures stay so they can be retried.
+pub fn restore_entry(store: &HistoryStore, entry_id: &str) -> Result<RestoreResult, CleanMacError> {
+    let outcomes = store
+        .update(|history| {
+            let entry = history.entries.iter_mut().find(|e| e.id == entry_id)?;
+            let outcomes: Vec<RestoreItemOutcome> =
+                entry.restore_journal.iter().map(restore).collect();
+            entry.restore_journal = entry
+                .restore_journal
+                .drain(..)
+                .zip(&outcomes)
+                .filter(|(_, o)| {
+                    matches!(o.status, RestoreStatus::Conflict | RestoreStatus::Failed)
+                })
+                .map(|(record, _)| record)
+                .collect();
+            Some(outcomes)
+        })?
+        .ok_or_else(|| {
+            CleanMacError::not_found(format!("No history entry with id {}", entry_id))
+        })?;
+
+    Ok(RestoreResult {
+        success: outcomes.iter().all(|o| o.status == RestoreStatus::Restored),
+        items_restored: outcomes
+            .iter()
+            .filter(|o| o.status == RestoreStatus::Restored)
+            .count() as u32,
+        items: outcomes,
+    })
+}
+
+/// Move a trashed item back to where it came from, never overwriting anything
+pub fn restore(record: &RestoreRecord) -> RestoreItemOutcome {
+    let outcome = |status, error: Option<String>| RestoreItemOutcome {
+        path: record.original_path.clone(),
+        status,
+        error,
+    };
+
+    let conflict = || {
+        outcome(
+            RestoreStatus::Conflict,
+            Some("Something already exists at the original path".to_string()),
+        )
+    };
+    if fs::symlink_metadata(&record.original_path).is_ok() {
+        return conflict();
+    }
+    if fs::symlink_metadata(&record.trashed_path).is_err() {
+        return outcome(
+            RestoreStatus::Missing,
+            Some("Item is no longer in the Trash".to_string()),
+        );
+    }
+
+    let moved = record
+        .original_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| rename_no_replace(&record.trashed_path, &record.original_path));
+    match moved {
+        Ok(()) => {}
+        // Something appeared at the original path since the check above
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return conflict(),
+        Err(e) => return outcome(RestoreStatus::Failed, Some(e.to_string())),
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(info) = info_for_trashed_file(&record.trashed_path) {
+        if let Err(e) = fs::remove_file(&info) {
+            log::debug!("Could not remove {}: {}", info.display(), e);
+        }
+    }
+
+    outcome(RestoreStatus::Restored, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::history::CleaningEntry;
+    use tempfile::tempdir;
+
+    fn record(dir: &Path) -> RestoreRecord {
+        let trashed = dir.join("Trash/files/app.log");
+        fs::create_dir_all(trashed.parent().unwrap()).unwrap();
+        fs::write(&trashed, b"log").unwrap();
+        RestoreRecord {
+            original_path: dir.join("Library/Logs/app.log"),
+            trashed_path: trashed,
+        }
+    }
+
+    #[test]
+    fn test_restore_moves_item_back() {
+        let dir = tempdir().unwrap();
+        let record = record(dir.path());
+
+        let outcome = restore(&record);
+        assert_eq!(outcome.status, RestoreStatus::Restored);
+        assert_eq!(fs::read(&record.original_path).unwrap(), b"log");
+        assert!(!record.trashed_path.exists());
+
+        assert_eq!(restore(&record).status, RestoreStatus::Conflict);
+    }
+
+    #[test]
+    fn test_restore_never_overwrites() {
+        let dir = tempdir().unwrap();
+        let record = record(dir.path());
+        fs::create_dir_all(record.original_path.parent().unwrap()).unwrap();
+        fs::write(&record.original_path, b"new").unwrap();
+
+        let outcome = restore(&record);
+        assert_eq!(outcome.status, RestoreStatus::Conflict);
+        assert_eq!(fs::read(&record.original_path).unwrap(), b"new");
+        assert!(record.trashed_path.exists());
+    }
+
+    #[test]
+    fn test_restore_missing_item() {
+        let dir = tempdir().unwrap();
+        let record = record(dir.path());
+        fs::remove_file(&record.trashed_path).unwrap();
+        assert_eq!(restore(&record).status, RestoreStatus::Missing);
+    }
+
+    #[test]
+    fn test_restore_entry_keeps_conflicts_in_journal() {
+        let dir = tempdir().unwrap();
+        let restorable = record(dir.path());
+        let conflicting = RestoreRecord {
+            original_path: dir.path().join("Desktop/notes.txt"),
+            trashed_path: dir.path().join("Trash/files/notes.txt"),
+        };
+        fs::write(&conflicting.trashed_path, b"old").unwrap();
+        fs::create_dir_all(dir.path().join("Desktop")).unwrap();
+        fs::write(&conflicting.original_path, b"new").unwrap();
+
+        let store = HistoryStore::in_dir(&dir.path().join("data"));
+        store
+            .append(CleaningEntry {
+                id: "abc".to_string(),
+                timestamp: 1,
+                space_reclaimed: 6,
+                items_cleaned: 2,
+                categories: vec![],
+                items: vec![],
+                restore_journal: vec![restorable.clone(), conflicting.clone()],
+                automatic: false,
+            })
+            .unwrap();
+
+        let result = restore_entry(&store, "abc").unwrap();
+        assert!(!result.success);
+        assert_eq!(result.items_restored, 1);
+        assert_eq!(result.items[1].status, RestoreStatus::Conflict);
+        assert!(restorable.original_path.exists());
+
+        let entry = &store.load().unwrap().entries[0];
+        assert_eq!(entry.restore_journal, vec![conflicting]);
+        assert!(restore_entry(&store, "missing").is_err());
+    }
+
+    #[test]
+    fn test_renamed_item_is_found_in_trash_listing() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("report.pdf"), b"older").unwrap();
+        let before = list_names(dir.path());
+        fs::write(dir.path().join("report 2.pdf"), b"newer").unwrap();
+
+        let name = OsStr::new("report.pdf");
+        assert_eq!(
+            added_entry(&before, list_names(dir.path()), name),
+            Some(OsString::from("report 2.pdf"))
+        );
+
+        // Two new entries and neither kept the name: nothing is guessed
+        fs::write(dir.path().join("other.txt"), b"other").unwrap();
+        assert_eq!(added_entry(&before, list_names(dir.path()), name), None);
+        assert!(added_entry(&HashSet::new(), list_names(dir.path()), name).is_some());
+    }
+
+    /// Points the freedesktop home trash at a folder until dropped
+    #[cfg(all(unix, not(target_os = "macos")))]
+    struct DataHome {
+        previous: Option<OsString>,
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    impl DataHome {
+        fn set(path: &Path) -> Self {
+            static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+            let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = std::env::var_os("XDG_DATA_HOME");
+            std::env::set_var("XDG_DATA_HOME", path);
+            Self {
+                previous,
+                _lock: lock,
+            }
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    impl Drop for DataHome {
+        fn drop(&mut self) {
+            match self.previous.take() {
+                Some(previous) => std::env::set_var("XDG_DATA_HOME", previous),
+                None => std::env::remove_var("XDG_DATA_HOME"),
+            }
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_freedesktop_trash_round_trip() {
+        // A home trash beside the file keeps the developer's own trash out of it
+        let dir = tempdir().unwrap();
+        let _data_home = DataHome::set(&dir.path().join("data"));
+        let path = dir.path().join("restore-me.txt");
+        fs::write(&path, b"data").unwrap();
+
+        assert_eq!(move_to_trash(&path, dir.path()).unwrap(), None);
+        let located = locate_trashed(std::slice::from_ref(&path));
+        let trashed = located.get(&path).unwrap().clone();
+        assert!(trashed.starts_with(dir.path().join("data/Trash")));
+        assert!(trashed.exists());
+
+        let outcome = restore(&RestoreRecord {
+            original_path: path.clone(),
+            trashed_path: trashed.clone(),
+        });
+        assert_eq!(outcome.status, RestoreStatus::Restored);
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+        assert!(!info_for_trashed_file(&trashed).unwrap().exists());
+    }
+}
@@ -0,0 +1,531 @@
+use crate::analyzer::cache_categorizer::{
+    assess_path, category_name, resolve_parent, PathAssessment,
+};
+use crate::analyzer::protection::ProtectionEngine;
+use crate::cleaner::restore::{locate_trashed, move_to_trash};
+use crate::error::CleanMacError;
+use crate::models::history::{
+    CleanItemOutcome, CleanMode, CleanPlan, CleanedItem, CleaningEntry, CleaningResult,
+    PlannedItem, RestoreRecord,
+};
+use crate::models::scan_result::SafetyLevel;
+use crate::scanner::now_timestamp;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::get_size;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Deletes user-selected paths after rechecking that each one is safe to remove
+pub struct Cleaner {
+    home: PathBuf,
+    exclusions: ExclusionMatcher,
+    protection: Option<ProtectionEngine>,
+    override_protection: bool,
+}
+
+impl Cleaner {
+    pub fn new(home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            exclusions: ExclusionMatcher::default(),
+            protection: None,
+            override_protection: false,
+        }
+    }
+
+    pub fn for_current_user() -> Option<Self> {
+        dirs::home_dir().map(Self::new)
+    }
+
+    /// Paths (and everything below them) the user excluded from cleaning
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    /// Refuse paths the active profile protects
+    pub fn with_protection(mut self, protection: ProtectionEngine) -> Self {
+        self.protection = Some(protection);
+        self
+    }
+
+    /// Clean profile-protected paths anyway, for when the user confirmed them
+    /// explicitly; built-in system protection still applies
+    pub fn override_protection(mut self, override_protection: bool) -> Self {
+        self.override_protection = override_protection;
+        self
+    }
+
+    /// Decide whether `path` may be removed, returning the assessment that allowed it
+    pub fn check(&self, path: &Path) -> Result<PathAssessment, CleanMacError> {
+        let refuse = |reason: String| CleanMacError::ProtectedPath {
+            reason,
+            path: path.to_path_buf(),
+        };
+
+        // Checked as given and with links resolved, so a link cannot route around a rule
+        let resolved = resolve_parent(path);
+        for candidate in [path, resolved.as_path()] {
+            let exclusion = self.exclusions.explain(candidate);
+            if exclusion.excluded {
+                return Err(refuse(format!(
+                    "Path is excluded from cleaning by {}",
+                    exclusion.rule.unwrap_or_default()
+                )));
+            }
+            if let Some(rule) = self.exclusions.excluded_inside(candidate) {
+                return Err(refuse(format!(
+                    "Contains a path excluded from cleaning by {}",
+                    rule
+                )));
+            }
+            if !self.override_protection {
+                if let Some(reason) = self
+                    .protection
+                    .as_ref()
+                    .and_then(|p| p.protection_reason(candidate))
+                {
+                    return Err(refuse(format!(
+                        "Protected by the active profile: {}",
+                        reason
+                    )));
+                }
+            }
+        }
+
+        let assessment = assess_path(path, &self.home);
+        if assessment.safety == SafetyLevel::Protected {
+            return Err(refuse(assessment.reason));
+        }
+        if fs::symlink_metadata(path).is_err() {
+            return Err(CleanMacError::NotFound {
+                message: "Path does not exist".to_string(),
+                path: Some(path.to_path_buf()),
+            });
+        }
+
+        Ok(assessment)
+    }
+
+    /// Clean every path, continuing past failures
+    pub fn clean(&self, paths: &[PathBuf], mode: CleanMode) -> CleaningResult {
+        let outcomes = unique(paths)
+            .map(|path| self.clean_one(path, mode))
+            .collect();
+        self.finish(outcomes, mode)
+    }
+
+    /// Work out what [`clean`](Self::clean) would do without removing anything
+    pub fn plan(&self, paths: &[PathBuf], mode: CleanMode) -> CleanPlan {
+        let items: Vec<PlannedItem> = unique(paths).map(|path| self.plan_one(path)).collect();
+        CleanPlan {
+            id: format!("plan-{}", new_entry_id()),
+            created_at: now_timestamp(),
+            mode,
+            total_size: items.iter().filter(|i| i.allowed).map(|i| i.size).sum(),
+            items,
+        }
+    }
+
+    /// Clean the allowed items of `plan`, skipping any that changed size since
+    /// it was made so nothing is removed that the user did not see
+    pub fn execute(&self, plan: &CleanPlan) -> CleaningResult {
+        let outcomes = plan
+            .items
+            .iter()
+            .filter(|item| item.allowed)
+            .map(|item| {
+                let mut outcome = self.prepare(&item.path);
+                if outcome.error.is_none() && outcome.size != item.size {
+                    outcome.error = Some("Changed since the plan was made".to_string());
+                }
+                self.remove_prepared(outcome, plan.mode)
+            })
+            .collect();
+        self.finish(outcomes, plan.mode)
+    }
+
+    fn plan_one(&self, path: &Path) -> PlannedItem {
+        let outcome = self.prepare(path);
+        let assessment = assess_path(path, &self.home);
+        let (allowed, safety, rule) = match outcome.error {
+            None => (true, assessment.safety, assessment.reason),
+            Some(error) if fs::symlink_metadata(path).is_err() => {
+                (false, SafetyLevel::Unknown, error)
+            }
+            Some(error) => (false, SafetyLevel::Protected, error),
+        };
+
+        PlannedItem {
+            path: outcome.path,
+            size: outcome.size,
+            category: outcome.category,
+            safety,
+            allowed,
+            rule,
+        }
+    }
+
+    /// Find where trashed items went and total up the run
+    fn finish(&self, mut outcomes: Vec<CleanItemOutcome>, mode: CleanMode) -> CleaningResult {
+        if mode == CleanMode::Trash {
+            let unlocated: Vec<PathBuf> = outcomes
+                .iter()
+                .filter(|o| o.success && o.trashed_path.is_none())
+                .map(|o| o.path.clone())
+                .collect();
+            let mut located = locate_trashed(&unlocated);
+            for outcome in outcomes.iter_mut().filter(|o| o.trashed_path.is_none()) {
+                outcome.trashed_path = located.remove(&outcome.path);
+            }
+        }
+
+        let succeeded = outcomes.iter().filter(|o| o.success);
+        CleaningResult {
+            success: outcomes.iter().all(|o| o.success),
+            space_reclaimed: succeeded.clone().map(|o| o.size).sum(),
+            items_cleaned: succeeded.count() as u32,
+            items: outcomes,
+        }
+    }
+
+    fn clean_one(&self, path: &Path, mode: CleanMode) -> CleanItemOutcome {
+        self.remove_prepared(self.prepare(path), mode)
+    }
+
+    /// Remove an item [`prepare`](Self::prepare) cleared, leaving refused ones alone
+    fn remove_prepared(&self, mut outcome: CleanItemOutcome, mode: CleanMode) -> CleanItemOutcome {
+        if outcome.error.is_some() {
+            return outcome;
+        }
+
+        let path = outcome.path.clone();
+        match remove(&path, mode, &self.home) {
+            Ok(trashed_path) => {
+                outcome.success = true;
+                outcome.trashed_path = trashed_path;
+            }
+            Err(e) => {
+                log::warn!("Failed to clean {}: {}", path.display(), e);
+                outcome.error = Some(e);
+            }
+        }
+        outcome
+    }
+
+    /// Check `path` and measure it, with `error` set if it may not be removed
+    fn prepare(&self, path: &Path) -> CleanItemOutcome {
+        let mut outcome = CleanItemOutcome {
+            path: path.to_path_buf(),
+            size: 0,
+            category: category_name(None),
+            success: false,
+            error: None,
+            trashed_path: None,
+        };
+
+        match self.check(path) {
+            Ok(assessment) => {
+                outcome.category = category_name(assessment.category.as_ref());
+                outcome.size = item_size(path);
+            }
+            Err(e) => outcome.error = Some(e.to_string()),
+        }
+        outcome
+    }
+}
+
+/// `paths` in order with repeats dropped
+fn unique(paths: &[PathBuf]) -> impl Iterator<Item = &PathBuf> {
+    let mut seen = HashSet::new();
+    paths.iter().filter(move |p| seen.insert(p.as_path()))
+}
+
+/// History entry for the items a run removed, or `None` if nothing was removed
+pub fn history_entry(result: &CleaningResult) -> Option<CleaningEntry> {
+    let items: Vec<CleanedItem> = result
+        .items
+        .iter()
+        .filter(|o| o.success)
+        .map(|o| CleanedItem {
+            path: o.path.clone(),
+            size: o.size,
+            category: o.category.clone(),
+        })
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut categories: Vec<String> = items.iter().map(|i| i.category.clone()).collect();
+    categories.sort();
+    categories.dedup();
+
+    let restore_journal = result
+        .items
+        .iter()
+        .filter(|o| o.success)
+        .filter_map(|o| {
+            Some(RestoreRecord {
+                original_path: o.path.clone(),
+                trashed_path: o.trashed_path.clone()?,
+            })
+        })
+        .collect();
+
+    Some(CleaningEntry {
+        id: new_entry_id(),
+        timestamp: now_timestamp(),
+        space_reclaimed: result.space_reclaimed,
+        items_cleaned: result.items_cleaned,
+        categories,
+        items,
+        restore_journal,
+        automatic: false,
+    })
+}
+
+/// Unique id derived from the current time
+pub(crate) fn new_entry_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}", nanos)
+}
+
+/// Size on disk, without following a symlink to its target
+fn item_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => metadata.len(),
+        Ok(_) => get_size(path).unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+/// Remove `path`, returning where it went in the Trash if that is already known
+fn remove(path: &Path, mode: CleanMode, home: &Path) -> Result<Option<PathBuf>, String> {
+    match mode {
+        CleanMode::Trash => move_to_trash(path, home).map_err(|e| e.to_string()),
+        CleanMode::Permanent => {
+            let metadata = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+            let result: io::Result<()> = if metadata.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            result.map(|_| None).map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{CustomProfile, UserProfile};
+    use crate::test_utils::write_filler;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_permanent_clean_reports_each_item() {
+        let home = tempdir().unwrap();
+        let cache = home.path().join("Library/Caches/com.vendor.App");
+        let log = home.path().join("Library/Logs/app.log");
+        let excluded = home.path().join("Library/Caches/com.keep.App");
+        let keychain = home.path().join("Library/Keychains/login.keychain-db");
+        write_filler(&cache.join("blob"), 100);
+        write_filler(&log, 20);
+        write_filler(&excluded.join("blob"), 5);
+        write_filler(&keychain, 5);
+
+        let cleaner = Cleaner::new(home.path())
+            .with_exclusions(ExclusionMatcher::new(std::slice::from_ref(&excluded)));
+        let result = cleaner.clean(
+            &[
+                cache.clone(),
+                log.clone(),
+                excluded.clone(),
+                keychain.clone(),
+                home.path().join("Library"),
+                home.path().join("missing"),
+                cache.clone(),
+            ],
+            CleanMode::Permanent,
+        );
+
+        assert!(!result.success);
+        assert_eq!(result.items.len(), 6);
+        assert_eq!(result.items_cleaned, 2);
+        assert_eq!(result.space_reclaimed, 120);
+        assert!(!cache.exists());
+        assert!(!log.exists());
+        assert!(excluded.exists());
+        assert!(keychain.exists());
+
+        assert_eq!(result.items[0].category, "Application");
+        assert_eq!(result.items[1].category, "Logs");
+        assert_eq!(
+            result.items[2].error,
+            Some(format!(
+                "Path is excluded from cleaning by {}",
+                excluded.display()
+            ))
+        );
+        assert!(result.items[3..].iter().all(|o| !o.success));
+
+        let entry = history_entry(&result).unwrap();
+        assert_eq!(entry.items.len(), 2);
+        assert_eq!(entry.space_reclaimed, 120);
+        assert_eq!(entry.categories, vec!["Application", "Logs"]);
+        assert!(!entry.id.is_empty());
+        assert!(entry.restore_journal.is_empty());
+    }
+
+    #[test]
+    fn test_protected_paths_and_their_ancestors_are_refused() {
+        let home = tempdir().unwrap();
+        let project = home.path().join("Projects/app");
+        write_filler(&project.join("target/out"), 10);
+
+        let profile = UserProfile::Custom(CustomProfile {
+            protect_developer_caches: false,
+            protected_paths: vec![project.join("target")],
+        });
+        let cleaner = Cleaner::new(home.path())
+            .with_protection(ProtectionEngine::new(home.path(), profile.clone()));
+        assert!(cleaner.check(&project.join("target/out")).is_err());
+        assert!(cleaner.check(&project).is_err());
+        assert!(cleaner.check(&project.join("target/../target")).is_err());
+
+        let cleaner = Cleaner::new(home.path())
+            .with_protection(ProtectionEngine::new(home.path(), profile))
+            .override_protection(true);
+        assert!(cleaner.check(&project.join("target/out")).is_ok());
+        assert!(cleaner.check(&project.join("target/../target")).is_err());
+    }
+
+    #[test]
+    fn test_developer_profile_keeps_cargo_registry() {
+        let home = tempdir().unwrap();
+        let registry = home.path().join(".cargo/registry");
+        write_filler(&registry.join("cache/index"), 10);
+
+        let cleaner = Cleaner::new(home.path())
+            .with_protection(ProtectionEngine::new(home.path(), UserProfile::Developer));
+        let result = cleaner.clean(std::slice::from_ref(&registry), CleanMode::Permanent);
+        assert!(!result.success);
+        assert!(result.items[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Protected by the active profile"));
+        assert!(registry.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_is_removed_not_its_target() {
+        let home = tempdir().unwrap();
+        let target = home.path().join("Movies/real.mov");
+        let link = home.path().join("Desktop/link.mov");
+        write_filler(&target, 1000);
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let result =
+            Cleaner::new(home.path()).clean(std::slice::from_ref(&link), CleanMode::Permanent);
+        assert!(result.success);
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(target.exists());
+        assert!(result.space_reclaimed < 1000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keychains_are_refused_by_any_spelling() {
+        let home = tempdir().unwrap();
+        let keychain = home.path().join("Library/Keychains/login.keychain-db");
+        write_filler(&keychain, 5);
+        std::os::unix::fs::symlink(home.path().join("Library"), home.path().join("lnk")).unwrap();
+
+        let cleaner = Cleaner::new(home.path());
+        for path in [
+            "library",
+            "library/Keychains",
+            "lnk/Keychains",
+            "lnk/../Library",
+        ] {
+            assert!(
+                cleaner.check(&home.path().join(path)).is_err(),
+                "{} should be refused",
+                path
+            );
+        }
+        let result = cleaner.clean(&[home.path().join("lnk/Keychains")], CleanMode::Permanent);
+        assert!(!result.success);
+        assert!(keychain.exists());
+    }
+
+    #[test]
+    fn test_failed_run_has_no_history_entry() {
+        let home = tempdir().unwrap();
+        let result =
+            Cleaner::new(home.path()).clean(&[home.path().join("nothing")], CleanMode::Trash);
+        assert!(!result.success);
+        assert!(history_entry(&result).is_none());
+    }
+
+    #[test]
+    fn test_plan_touches_nothing() {
+        let home = tempdir().unwrap();
+        let cache = home.path().join("Library/Caches/com.vendor.App");
+        let excluded = home.path().join("Library/Caches/com.keep.App");
+        write_filler(&cache.join("blob"), 100);
+        write_filler(&excluded.join("blob"), 5);
+
+        let cleaner = Cleaner::new(home.path())
+            .with_exclusions(ExclusionMatcher::new(&[PathBuf::from("**/com.keep.App")]));
+        let plan = cleaner.plan(
+            &[cache.clone(), excluded.clone(), home.path().join("missing")],
+            CleanMode::Permanent,
+        );
+
+        assert_eq!(plan.total_size, 100);
+        assert!(plan.items[0].allowed);
+        assert_eq!(plan.items[0].safety, SafetyLevel::Safe);
+        assert!(!plan.items[1].allowed);
+        assert_eq!(plan.items[1].safety, SafetyLevel::Protected);
+        assert_eq!(
+            plan.items[1].rule,
+            "Path is excluded from cleaning by **/com.keep.App"
+        );
+        assert_eq!(plan.items[2].safety, SafetyLevel::Unknown);
+        assert!(cache.join("blob").exists());
+    }
+
+    #[test]
+    fn test_execute_skips_items_changed_since_planning() {
+        let home = tempdir().unwrap();
+        let stable = home.path().join("Library/Caches/com.vendor.Stable");
+        let growing = home.path().join("Library/Caches/com.vendor.Growing");
+        write_filler(&stable.join("blob"), 100);
+        write_filler(&growing.join("blob"), 100);
+
+        let cleaner = Cleaner::new(home.path());
+        let plan = cleaner.plan(&[stable.clone(), growing.clone()], CleanMode::Permanent);
+        write_filler(&growing.join("new"), 10);
+
+        let result = cleaner.execute(&plan);
+        assert!(!result.success);
+        assert_eq!(result.items_cleaned, 1);
+        assert!(!stable.exists());
+        assert!(growing.exists());
+        assert_eq!(
+            result.items[1].error.as_deref(),
+            Some("Changed since the plan was made")
+        );
+    }
+}
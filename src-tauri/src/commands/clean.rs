@@ -0,0 +1,139 @@
+use crate::analyzer::protection::ProtectionEngine;
+use crate::cleaner::history::HistoryStore;
+use crate::cleaner::plan::PlanStore;
+use crate::cleaner::restore::restore_entry;
+use crate::cleaner::safe_delete::{history_entry, Cleaner};
+use crate::commands::config::load_config;
+use crate::error::CleanMacError;
+use crate::models::history::{
+    CleanMode, CleanPlan, CleaningResult, HistoryPage, HistoryQuery, HistorySummary, RestoreResult,
+};
+use crate::utils::exclusions::ExclusionMatcher;
+use std::path::PathBuf;
+use tauri::{command, AppHandle, Manager, State};
+
+/// Clean `paths`, refusing anything the profile protects unless
+/// `override_protection` is set
+#[command]
+pub async fn clean_items(
+    app: AppHandle,
+    paths: Vec<PathBuf>,
+    mode: CleanMode,
+    override_protection: Option<bool>,
+) -> Result<CleaningResult, CleanMacError> {
+    let cleaner = build_cleaner(&app, override_protection)?;
+    let store = HistoryStore::in_dir(&app.path().app_data_dir()?);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = cleaner.clean(&paths, mode);
+        record_history(&store, &result);
+        result
+    })
+    .await
+    .map_err(CleanMacError::from)
+}
+
+/// Work out what `clean_items` would do with the same input, without touching
+/// the disk; confirm the plan with `execute_clean_plan`
+#[command]
+pub async fn plan_clean(
+    app: AppHandle,
+    plans: State<'_, PlanStore>,
+    paths: Vec<PathBuf>,
+    mode: CleanMode,
+    override_protection: Option<bool>,
+) -> Result<CleanPlan, CleanMacError> {
+    let cleaner = build_cleaner(&app, override_protection)?;
+
+    let (plan, cleaner) = tauri::async_runtime::spawn_blocking(move || {
+        let plan = cleaner.plan(&paths, mode);
+        (plan, cleaner)
+    })
+    .await?;
+    plans.insert(plan.clone(), cleaner);
+    Ok(plan)
+}
+
+/// Clean the allowed items of a plan from `plan_clean`, once
+#[command]
+pub async fn execute_clean_plan(
+    app: AppHandle,
+    plans: State<'_, PlanStore>,
+    plan_id: String,
+) -> Result<CleaningResult, CleanMacError> {
+    let (plan, cleaner) = plans.take(&plan_id)?;
+    let store = HistoryStore::in_dir(&app.path().app_data_dir()?);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = cleaner.execute(&plan);
+        record_history(&store, &result);
+        result
+    })
+    .await
+    .map_err(CleanMacError::from)
+}
+
+/// Cleaner enforcing the saved exclusions and profile
+fn build_cleaner(
+    app: &AppHandle,
+    override_protection: Option<bool>,
+) -> Result<Cleaner, CleanMacError> {
+    let config = load_config(app)?;
+    let protection = ProtectionEngine::for_current_user(config.user_profile)
+        .ok_or_else(CleanMacError::home_not_found)?;
+    Ok(Cleaner::for_current_user()
+        .ok_or_else(CleanMacError::home_not_found)?
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+        .with_protection(protection)
+        .override_protection(override_protection.unwrap_or(false)))
+}
+
+fn record_history(store: &HistoryStore, result: &CleaningResult) {
+    if let Some(entry) = history_entry(result) {
+        // The files are already gone, so report the result even if history can't be saved
+        if let Err(e) = store.append(entry) {
+            log::warn!("Failed to record cleaning history: {}", e);
+        }
+    }
+}
+
+/// Past cleanings, newest first, filtered and paginated by `query`
+#[command]
+pub async fn get_cleaning_history(
+    app: AppHandle,
+    query: Option<HistoryQuery>,
+) -> Result<HistoryPage, CleanMacError> {
+    let store = HistoryStore::in_dir(&app.path().app_data_dir()?);
+    let query = query.unwrap_or_default();
+
+    tauri::async_runtime::spawn_blocking(move || store.list(&query).map_err(CleanMacError::from))
+        .await?
+}
+
+/// Space reclaimed per local calendar month and per category
+#[command]
+pub async fn get_history_summary(
+    app: AppHandle,
+    query: Option<HistoryQuery>,
+) -> Result<HistorySummary, CleanMacError> {
+    let store = HistoryStore::in_dir(&app.path().app_data_dir()?);
+    let query = query.unwrap_or_default();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        store
+            .summary(&query, &chrono::Local)
+            .map_err(CleanMacError::from)
+    })
+    .await?
+}
+
+/// Move the items of a past Trash cleaning back to their original paths
+#[command]
+pub async fn restore_cleaning_entry(
+    app: AppHandle,
+    entry_id: String,
+) -> Result<RestoreResult, CleanMacError> {
+    let store = HistoryStore::in_dir(&app.path().app_data_dir()?);
+
+    tauri::async_runtime::spawn_blocking(move || restore_entry(&store, &entry_id)).await?
+}
@@ -0,0 +1,48 @@
+use crate::error::CleanMacError;
+use crate::models::config::{AppConfig, ExclusionExplanation};
+use crate::utils::config_store::ConfigStore;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::expand_tilde;
+use std::path::PathBuf;
+use tauri::{command, AppHandle, Manager};
+
+fn config_store(app: &AppHandle) -> Result<ConfigStore, CleanMacError> {
+    let dir = app.path().app_config_dir()?;
+    Ok(ConfigStore::in_dir(&dir))
+}
+
+/// Saved settings, with defaults for anything not yet saved
+///
+/// Before the first save the profile follows the detected developer tools.
+#[command]
+pub fn get_config(app: AppHandle) -> Result<AppConfig, CleanMacError> {
+    Ok(config_store(&app)?.load_or_detect()?)
+}
+
+/// The settings scans and cleaning apply: what `get_config` returns, validated
+///
+/// Commands never take settings from the caller, so the exclusions and
+/// protection they enforce are the ones the user saved.
+pub(crate) fn load_config(app: &AppHandle) -> Result<AppConfig, CleanMacError> {
+    config_store(app)?.load_validated()
+}
+
+/// Validate and save the settings, returning what was stored
+#[command]
+pub fn update_config(app: AppHandle, config: AppConfig) -> Result<AppConfig, CleanMacError> {
+    config.validate().map_err(CleanMacError::ConfigInvalid)?;
+    config_store(&app)?.save(&config)?;
+    Ok(config)
+}
+
+/// Whether the saved exclusions keep `path` out of scans and cleaning, and
+/// which pattern decided it
+#[command]
+pub fn explain_exclusion(
+    app: AppHandle,
+    path: PathBuf,
+) -> Result<ExclusionExplanation, CleanMacError> {
+    let config = load_config(&app)?;
+    let path = expand_tilde(&path.to_string_lossy());
+    Ok(ExclusionMatcher::new(&config.exclusions).explain(&path))
+}
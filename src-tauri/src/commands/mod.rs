@@ -1 +1,4 @@
+pub mod clean;
+pub mod config;
+pub mod scan;
 pub mod system;
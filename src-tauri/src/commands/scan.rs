@@ -0,0 +1,281 @@
+use crate::analyzer::protection::ProtectionEngine;
+use crate::commands::config::load_config;
+use crate::error::CleanMacError;
+use crate::models::scan_result::{
+    CacheScanResult, DuplicateFolderScanResult, DuplicateScanResult, LargeFileScanResult,
+    OriginalSelection, OrphanScanResult, SimilarImageScanResult, UsageNode, UsageTree,
+};
+use crate::scanner::cache_scanner::CacheScanner;
+use crate::scanner::dir_index::DirIndexStore;
+use crate::scanner::duplicate_folder_scanner::DuplicateFolderScanner;
+use crate::scanner::duplicate_scanner::DuplicateScanner;
+use crate::scanner::hash_cache::{HashCache, HashCacheStore};
+use crate::scanner::large_file_scanner::LargeFileScanner;
+use crate::scanner::orphan_scanner::OrphanScanner;
+use crate::scanner::progress::{ScanContext, ScanRegistry, SCAN_PROGRESS_EVENT};
+use crate::scanner::similar_image_scanner::SimilarImageScanner;
+use crate::scanner::usage_tree::{SizedTree, UsageTreeStore};
+use crate::utils::exclusions::ExclusionMatcher;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+/// Register a scan so `cancel_scan` can reach it and forward its progress as events
+///
+/// Callers pass their own `scan_id` so they can cancel the scan while it runs.
+fn start_scan(
+    app: &AppHandle,
+    registry: &ScanRegistry,
+    scan_id: Option<String>,
+) -> Arc<ScanContext> {
+    let scan_id = scan_id.unwrap_or_else(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        format!("scan-{:x}", nanos)
+    });
+    let app = app.clone();
+    let ctx = Arc::new(ScanContext::new(scan_id).with_reporter(move |progress| {
+        if let Err(e) = app.emit(SCAN_PROGRESS_EVENT, progress) {
+            log::debug!("Failed to emit scan progress: {}", e);
+        }
+    }));
+    registry.register(ctx.clone());
+    ctx
+}
+
+/// Stop a running scan; the scan command then returns what it found so far
+/// with `incomplete` set. Returns false if no scan with that id is running.
+#[command]
+pub fn cancel_scan(registry: State<'_, ScanRegistry>, scan_id: String) -> bool {
+    registry.cancel(&scan_id)
+}
+
+fn dir_index_store(app: &AppHandle) -> Result<DirIndexStore, CleanMacError> {
+    let data_dir = app.path().app_data_dir()?;
+    Ok(DirIndexStore::in_dir(&data_dir))
+}
+
+fn hash_cache_store(app: &AppHandle) -> Result<HashCacheStore, CleanMacError> {
+    let data_dir = app.path().app_data_dir()?;
+    Ok(HashCacheStore::in_dir(&data_dir))
+}
+
+/// Load the hash cache, falling back to an empty one since it only speeds scans up
+fn load_hash_cache(store: &HashCacheStore) -> Arc<HashCache> {
+    Arc::new(store.load().unwrap_or_else(|e| {
+        log::warn!("Failed to load hash cache: {}", e);
+        Default::default()
+    }))
+}
+
+fn save_hash_cache(store: &HashCacheStore, cache: &HashCache) {
+    if let Err(e) = store.save(cache) {
+        log::warn!("Failed to save hash cache: {}", e);
+    }
+}
+
+/// Scan caches, marking what the saved profile protects
+///
+/// Directory sizes are kept in an index between scans, so a rescan after
+/// cleaning only reads what changed.
+#[command]
+pub async fn scan_caches(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_id: Option<String>,
+) -> Result<CacheScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let scanner = CacheScanner::for_current_user()
+        .ok_or_else(CleanMacError::home_not_found)?
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions));
+    let protection = ProtectionEngine::for_current_user(config.user_profile)
+        .ok_or_else(CleanMacError::home_not_found)?;
+    let store = dir_index_store(&app)?;
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    // Walking caches can take a while, keep it off the main thread
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        // The index only speeds things up, so scan without it rather than fail
+        let index = Arc::new(store.load().unwrap_or_else(|e| {
+            log::warn!("Failed to load directory index: {}", e);
+            Default::default()
+        }));
+        let mut result = scanner.with_index(index.clone()).scan_with(&scan_ctx);
+        if let Err(e) = store.save(&index) {
+            log::warn!("Failed to save directory index: {}", e);
+        }
+        protection.apply(&mut result);
+        result
+    })
+    .await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+#[command]
+pub async fn scan_orphaned_apps(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_id: Option<String>,
+) -> Result<OrphanScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let scanner = OrphanScanner::for_current_user()
+        .ok_or_else(CleanMacError::home_not_found)?
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions));
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || scanner.scan_with(&scan_ctx)).await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+#[command]
+pub async fn scan_large_files(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_id: Option<String>,
+) -> Result<LargeFileScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let home = dirs::home_dir().ok_or_else(CleanMacError::home_not_found)?;
+    let scanner = LargeFileScanner::from_config(&config, &home);
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || scanner.scan_with(&scan_ctx)).await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+/// Find identical files below `scan_paths`
+///
+/// File hashes are kept between scans, so a rescan only reads files whose
+/// size or modification time changed.
+#[command]
+pub async fn scan_duplicates(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_paths: Vec<PathBuf>,
+    selection: Option<OriginalSelection>,
+    scan_id: Option<String>,
+) -> Result<DuplicateScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let scanner = DuplicateScanner::new(scan_paths)
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+        .with_hash_algorithm(config.hash_algorithm)
+        .with_selection(selection.unwrap_or_default());
+    let store = hash_cache_store(&app)?;
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let cache = load_hash_cache(&store);
+        let result = scanner.with_hash_cache(cache.clone()).scan_with(&scan_ctx);
+        save_hash_cache(&store, &cache);
+        result
+    })
+    .await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+/// Folders with identical trees, plus pairs sharing at least
+/// `similarity_threshold` of their bytes (90% by default)
+#[command]
+pub async fn scan_duplicate_folders(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_paths: Vec<PathBuf>,
+    selection: Option<OriginalSelection>,
+    similarity_threshold: Option<f64>,
+    scan_id: Option<String>,
+) -> Result<DuplicateFolderScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let mut scanner = DuplicateFolderScanner::new(scan_paths)
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+        .with_hash_algorithm(config.hash_algorithm)
+        .with_selection(selection.unwrap_or_default());
+    if let Some(threshold) = similarity_threshold {
+        scanner = scanner.with_similarity_threshold(threshold);
+    }
+    let store = hash_cache_store(&app)?;
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let cache = load_hash_cache(&store);
+        let result = scanner.with_hash_cache(cache.clone()).scan_with(&scan_ctx);
+        save_hash_cache(&store, &cache);
+        result
+    })
+    .await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+/// Images below `scan_paths` that look alike, grouped when their perceptual
+/// hashes differ in at most `max_distance` bits
+#[command]
+pub async fn scan_similar_images(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    scan_paths: Vec<PathBuf>,
+    max_distance: Option<u32>,
+    scan_id: Option<String>,
+) -> Result<SimilarImageScanResult, CleanMacError> {
+    let config = load_config(&app)?;
+    let mut scanner = SimilarImageScanner::new(scan_paths)
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions));
+    if let Some(max_distance) = max_distance {
+        scanner = scanner.with_max_distance(max_distance);
+    }
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || scanner.scan_with(&scan_ctx)).await;
+    registry.unregister(ctx.scan_id());
+    result.map_err(CleanMacError::from)
+}
+
+/// Sizes below `root` as a tree `depth` levels deep, largest first, with
+/// entries under `min_size` bytes gathered into an "Other" node
+///
+/// The measured tree is kept, so deeper levels load through
+/// `expand_usage_node` without walking the disk again.
+#[command]
+pub async fn disk_usage_tree(
+    app: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    trees: State<'_, UsageTreeStore>,
+    root: PathBuf,
+    depth: usize,
+    min_size: u64,
+    scan_id: Option<String>,
+) -> Result<UsageTree, CleanMacError> {
+    let ctx = start_scan(&app, &registry, scan_id);
+
+    let scan_ctx = ctx.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        SizedTree::build(&root, min_size, &scan_ctx).map_err(|e| CleanMacError::io(e, &root))
+    })
+    .await;
+    registry.unregister(ctx.scan_id());
+
+    let tree = Arc::new(result??);
+    trees.insert(tree.clone());
+    Ok(tree.to_tree(depth))
+}
+
+/// A node from `disk_usage_tree` with its children `depth` levels deep
+#[command]
+pub fn expand_usage_node(
+    trees: State<'_, UsageTreeStore>,
+    node_id: String,
+    depth: usize,
+) -> Result<UsageNode, CleanMacError> {
+    trees.expand(&node_id, depth)
+}
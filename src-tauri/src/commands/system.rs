@@ -1,9 +1,14 @@
+use crate::analyzer::developer_detector::DeveloperDetector;
+use crate::error::CleanMacError;
+use crate::models::config::DeveloperEnvironment;
+use crate::models::scan_result::PermissionReport;
 use crate::utils::permissions;
+use crate::utils::volumes::{self, VolumeInfo};
 use serde::Serialize;
+use std::path::Path;
 #[cfg(not(target_os = "macos"))]
 use std::path::PathBuf;
-use sysinfo::Disks;
-use tauri::command;
+use tauri::{command, AppHandle};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,46 +20,68 @@ pub struct DiskInfo {
     pub name: String,
 }
 
+/// Space on the volume holding the user's data
 #[command]
-pub fn get_disk_info() -> Result<DiskInfo, String> {
-    let disks = Disks::new_with_refreshed_list();
-
-    // On macOS, the system disk is usually mounted at "/"
-    // We'll find the disk mounted at "/" or take the first one
-    let disk = disks
-        .list()
-        .iter()
-        .find(|d| d.mount_point().to_string_lossy() == "/")
-        .or_else(|| disks.list().first())
-        .ok_or("No disks found")?;
-
-    let total_space = disk.total_space();
-    let available_space = disk.available_space();
-    let used_space = total_space.saturating_sub(available_space);
+pub fn get_disk_info() -> Result<DiskInfo, CleanMacError> {
+    let volumes = volumes::list_volumes();
+    let volume =
+        volumes::root_volume(&volumes).ok_or_else(|| CleanMacError::not_found("No disks found"))?;
 
     Ok(DiskInfo {
-        total_space,
-        available_space,
-        used_space,
-        mount_point: disk.mount_point().to_string_lossy().to_string(),
-        name: disk.name().to_string_lossy().to_string(),
+        total_space: volume.total_space,
+        available_space: volume.available_space,
+        used_space: volume.used_space,
+        mount_point: volume.mount_point.to_string_lossy().to_string(),
+        name: volume.name.clone(),
     })
 }
 
+/// Every mounted volume, including external and read-only ones
+#[command]
+pub fn list_volumes() -> Vec<VolumeInfo> {
+    volumes::list_volumes()
+}
+
+/// Installed developer tools and the size of their caches
+#[command]
+pub async fn detect_developer_environment() -> Result<DeveloperEnvironment, CleanMacError> {
+    let detector =
+        DeveloperDetector::for_current_user().ok_or_else(CleanMacError::home_not_found)?;
+    tauri::async_runtime::spawn_blocking(move || detector.detect())
+        .await
+        .map_err(CleanMacError::from)
+}
+
 #[command]
 pub fn check_full_disk_access() -> bool {
     permissions::check_full_disk_access()
 }
 
+/// Access to every location the scanners read, and which scans miss data
+/// where access is denied
+#[command]
+pub fn permission_report(app: AppHandle) -> Result<PermissionReport, CleanMacError> {
+    let config = crate::commands::config::load_config(&app)?;
+    let home = dirs::home_dir().ok_or_else(CleanMacError::home_not_found)?;
+    let volumes = if config.scan_locations.include_external_volumes {
+        volumes::list_volumes()
+    } else {
+        Vec::new()
+    };
+    let roots = volumes::scan_roots(&config.scan_locations, &home, &volumes);
+    let probes = permissions::default_probes(&home, &roots);
+    Ok(permissions::permission_report(&probes))
+}
+
 #[command]
-pub fn open_full_disk_access_settings() -> Result<(), String> {
+pub fn open_full_disk_access_settings() -> Result<(), CleanMacError> {
     #[cfg(target_os = "macos")]
     {
         // Deep link to Full Disk Access settings
         // macOS 13+ (Ventura) uses a different URL scheme than older versions, but this usually redirects correctly
         // x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles
         open::that("x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles")
-            .map_err(|e| e.to_string())
+            .map_err(CleanMacError::from)
     }
     #[cfg(not(target_os = "macos"))]
     {
@@ -63,15 +90,15 @@ pub fn open_full_disk_access_settings() -> Result<(), String> {
 }
 
 #[command]
-pub fn reveal_in_finder(path: String) -> Result<(), String> {
+pub fn reveal_in_finder(path: String) -> Result<(), CleanMacError> {
     #[cfg(target_os = "macos")]
     {
         // Use "open -R <path>" to reveal in Finder
         std::process::Command::new("open")
             .arg("-R")
-            .arg(path)
+            .arg(&path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| CleanMacError::io(e, Path::new(&path)))?;
         Ok(())
     }
     #[cfg(not(target_os = "macos"))]
@@ -79,7 +106,7 @@ pub fn reveal_in_finder(path: String) -> Result<(), String> {
         // Fallback for other OS (open parent dir)
         let p = PathBuf::from(path);
         if let Some(parent) = p.parent() {
-            open::that(parent).map_err(|e| e.to_string())
+            open::that(parent).map_err(|e| CleanMacError::io(e, parent))
         } else {
             Ok(())
         }
@@ -87,8 +114,8 @@ pub fn reveal_in_finder(path: String) -> Result<(), String> {
 }
 
 #[command]
-pub fn open_file(path: String) -> Result<(), String> {
-    open::that(path).map_err(|e| e.to_string())
+pub fn open_file(path: String) -> Result<(), CleanMacError> {
+    open::that(&path).map_err(|e| CleanMacError::io(e, Path::new(&path)))
 }
 
 #[cfg(test)]
@@ -0,0 +1,162 @@
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Error returned by commands
+///
+/// Serializes as `{ kind, message, path }` so the frontend can react to the
+/// kind, for example by asking for Full Disk Access on `PermissionDenied`.
+#[derive(Debug, Error)]
+pub enum CleanMacError {
+    #[error("{message}")]
+    PermissionDenied {
+        message: String,
+        path: Option<PathBuf>,
+    },
+    #[error("{message}")]
+    NotFound {
+        message: String,
+        path: Option<PathBuf>,
+    },
+    #[error("{reason}")]
+    ProtectedPath { reason: String, path: PathBuf },
+    #[error("{message}")]
+    Io {
+        message: String,
+        path: Option<PathBuf>,
+    },
+    #[error("{0}")]
+    ConfigInvalid(String),
+}
+
+/// The variant of a [`CleanMacError`], as sent to the frontend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CleanMacErrorKind {
+    PermissionDenied,
+    NotFound,
+    ProtectedPath,
+    Io,
+    ConfigInvalid,
+}
+
+impl CleanMacError {
+    /// Classify an IO error that happened at `path`
+    pub fn io(error: io::Error, path: &Path) -> Self {
+        let message = format!("{}: {}", path.display(), error);
+        let path = Some(path.to_path_buf());
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { message, path },
+            io::ErrorKind::NotFound => Self::NotFound { message, path },
+            _ => Self::Io { message, path },
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn home_not_found() -> Self {
+        Self::not_found("Could not determine home directory")
+    }
+
+    pub fn kind(&self) -> CleanMacErrorKind {
+        match self {
+            Self::PermissionDenied { .. } => CleanMacErrorKind::PermissionDenied,
+            Self::NotFound { .. } => CleanMacErrorKind::NotFound,
+            Self::ProtectedPath { .. } => CleanMacErrorKind::ProtectedPath,
+            Self::Io { .. } => CleanMacErrorKind::Io,
+            Self::ConfigInvalid(_) => CleanMacErrorKind::ConfigInvalid,
+        }
+    }
+
+    /// The path the error is about, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::PermissionDenied { path, .. }
+            | Self::NotFound { path, .. }
+            | Self::Io { path, .. } => path.as_deref(),
+            Self::ProtectedPath { path, .. } => Some(path),
+            Self::ConfigInvalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CleanMacError {
+    fn from(error: io::Error) -> Self {
+        let message = error.to_string();
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied {
+                message,
+                path: None,
+            },
+            io::ErrorKind::NotFound => Self::NotFound {
+                message,
+                path: None,
+            },
+            _ => Self::Io {
+                message,
+                path: None,
+            },
+        }
+    }
+}
+
+impl From<tauri::Error> for CleanMacError {
+    fn from(error: tauri::Error) -> Self {
+        Self::Io {
+            message: error.to_string(),
+            path: None,
+        }
+    }
+}
+
+impl Serialize for CleanMacError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CleanMacError", 3)?;
+        state.serialize_field("kind", &self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("path", &self.path())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serializes_kind_message_and_path() {
+        let error = CleanMacError::io(
+            io::Error::from(io::ErrorKind::PermissionDenied),
+            Path::new("/Library/Mail"),
+        );
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(value["kind"], "PermissionDenied");
+        assert_eq!(value["path"], "/Library/Mail");
+        assert!(value["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("/Library/Mail: "));
+
+        let value = serde_json::to_value(CleanMacError::ConfigInvalid("Bad".into())).unwrap();
+        assert_eq!(
+            value,
+            json!({ "kind": "ConfigInvalid", "message": "Bad", "path": null })
+        );
+    }
+
+    #[test]
+    fn test_io_errors_are_classified() {
+        let missing = CleanMacError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(missing.kind(), CleanMacErrorKind::NotFound);
+        let other = CleanMacError::from(io::Error::other("disk on fire"));
+        assert_eq!(other.kind(), CleanMacErrorKind::Io);
+        assert_eq!(other.to_string(), "disk on fire");
+    }
+}
@@ -1,9 +1,18 @@
 // CleanMac - macOS disk cleanup and optimization utility
 
+pub mod analyzer;
+pub mod cleaner;
 pub mod commands;
+pub mod error;
 pub mod models;
+pub mod scanner;
 pub mod utils;
 
+#[cfg(test)]
+mod test_utils;
+
+use cleaner::auto_clean::AutoCleaner;
+use tauri::Manager;
 use utils::format::{format_bytes, format_relative_time};
 
 /// Placeholder greeting command for initial testing
@@ -28,15 +37,47 @@ fn get_relative_time(timestamp: i64) -> String {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(scanner::progress::ScanRegistry::default())
+        .manage(cleaner::plan::PlanStore::default())
+        .manage(scanner::usage_tree::UsageTreeStore::default())
+        .setup(|app| {
+            let config_dir = app.path().app_config_dir()?;
+            let data_dir = app.path().app_data_dir()?;
+            if let Some(auto) = AutoCleaner::for_current_user(&config_dir, &data_dir) {
+                cleaner::auto_clean::spawn_scheduler(auto)?;
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             format_size,
             get_relative_time,
             commands::system::get_disk_info,
+            commands::system::list_volumes,
+            commands::system::detect_developer_environment,
             commands::system::check_full_disk_access,
+            commands::system::permission_report,
             commands::system::open_full_disk_access_settings,
             commands::system::reveal_in_finder,
-            commands::system::open_file
+            commands::system::open_file,
+            commands::scan::scan_caches,
+            commands::scan::scan_orphaned_apps,
+            commands::scan::scan_large_files,
+            commands::scan::scan_duplicates,
+            commands::scan::scan_duplicate_folders,
+            commands::scan::scan_similar_images,
+            commands::scan::cancel_scan,
+            commands::scan::disk_usage_tree,
+            commands::scan::expand_usage_node,
+            commands::clean::clean_items,
+            commands::clean::plan_clean,
+            commands::clean::execute_clean_plan,
+            commands::clean::get_cleaning_history,
+            commands::clean::get_history_summary,
+            commands::clean::restore_cleaning_entry,
+            commands::config::get_config,
+            commands::config::update_config,
+            commands::config::explain_exclusion
         ])
         .run(tauri::generate_context!())
         .expect("error while running CleanMac application");
@@ -1,18 +1,96 @@
+use crate::models::scan_result::CacheCategoryType;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::expand_tilde;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+// Mirrors the limits in the frontend's lib/constants.ts
+pub const DEFAULT_LARGE_FILE_THRESHOLD_MB: u64 = 100;
+pub const MIN_LARGE_FILE_THRESHOLD_MB: u64 = 10;
+pub const MAX_LARGE_FILE_THRESHOLD_MB: u64 = 10000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub user_profile: UserProfile,
+    /// Paths and patterns kept out of scans and cleaning, see `ExclusionMatcher`
     pub exclusions: Vec<PathBuf>,
     pub large_file_threshold_mb: u64,
     pub auto_clean: AutoCleanConfig,
     pub appearance: AppearanceConfig,
     pub scan_locations: ScanLocations,
+    /// Algorithm duplicate scans compare file contents with
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            user_profile: UserProfile::default(),
+            exclusions: Vec::new(),
+            large_file_threshold_mb: DEFAULT_LARGE_FILE_THRESHOLD_MB,
+            auto_clean: AutoCleanConfig {
+                enabled: false,
+                schedule: AutoCleanSchedule::OnDemand,
+                categories: Vec::new(),
+                min_age_days: 30,
+            },
+            appearance: AppearanceConfig {
+                theme: Theme::System,
+                show_menu_bar_icon: true,
+            },
+            scan_locations: ScanLocations {
+                include_external_volumes: false,
+                custom_scan_paths: Vec::new(),
+            },
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Reject settings the scanners and the frontend can't work with
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_LARGE_FILE_THRESHOLD_MB..=MAX_LARGE_FILE_THRESHOLD_MB)
+            .contains(&self.large_file_threshold_mb)
+        {
+            return Err(format!(
+                "Large file threshold must be between {} and {} MB",
+                MIN_LARGE_FILE_THRESHOLD_MB, MAX_LARGE_FILE_THRESHOLD_MB
+            ));
+        }
+
+        for pattern in &self.exclusions {
+            ExclusionMatcher::validate_pattern(&pattern.to_string_lossy())?;
+        }
+        for path in &self.scan_locations.custom_scan_paths {
+            if !expand_tilde(&path.to_string_lossy()).is_absolute() {
+                return Err(format!("Path must be absolute: {}", path.display()));
+            }
+        }
+
+        if let AutoCleanSchedule::OnLowDiskSpace { threshold_gb: 0 } = self.auto_clean.schedule {
+            return Err("Low disk space threshold must be at least 1 GB".to_string());
+        }
+
+        Ok(())
+    }
 }
 
+/// Whether a path is excluded from scans and cleaning, and why
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExclusionExplanation {
+    pub path: PathBuf,
+    pub excluded: bool,
+    /// The deciding pattern as written in the settings, if any matched
+    pub rule: Option<String>,
+    /// Where the pattern matched: `path` itself or one of its parent folders
+    pub matched_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum UserProfile {
+    #[default]
     Regular,
     Developer,
     Custom(CustomProfile),
@@ -28,8 +106,7 @@ pub struct CustomProfile {
 pub struct AutoCleanConfig {
     pub enabled: bool,
     pub schedule: AutoCleanSchedule,
-    // We use String here to avoid circular deps, but typically it maps to CacheCategoryType
-    pub categories: Vec<String>,
+    pub categories: Vec<CacheCategoryType>,
     pub min_age_days: u32,
 }
 
@@ -61,6 +138,18 @@ pub struct ScanLocations {
     pub custom_scan_paths: Vec<PathBuf>,
 }
 
+/// Hash used to tell whether files have the same contents
+///
+/// BLAKE3 and xxh3 are several times faster than SHA-256 on large files;
+/// xxh3 is not cryptographic but its 128-bit digest is ample for this.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+    Xxh3,
+}
+
 // Developer Environment (for profile detection)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeveloperEnvironment {
@@ -116,6 +205,7 @@ mod tests {
                 include_external_volumes: false,
                 custom_scan_paths: vec![],
             },
+            hash_algorithm: HashAlgorithm::Blake3,
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -123,5 +213,39 @@ mod tests {
 
         assert!(matches!(deserialized.user_profile, UserProfile::Developer));
         assert_eq!(deserialized.appearance.theme, Theme::Dark);
+        assert_eq!(deserialized.hash_algorithm, HashAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(AppConfig::default().validate().is_ok());
+
+        let mut config = AppConfig {
+            large_file_threshold_mb: 5,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        config.large_file_threshold_mb = MAX_LARGE_FILE_THRESHOLD_MB;
+        assert!(config.validate().is_ok());
+
+        config.exclusions = vec![
+            PathBuf::from("~/Projects"),
+            PathBuf::from("/Volumes/Backup"),
+        ];
+        assert!(config.validate().is_ok());
+        config.exclusions.push(PathBuf::from("**/node_modules"));
+        assert!(config.validate().is_ok());
+        config
+            .exclusions
+            .push(PathBuf::from("~/Projects/[unclosed"));
+        assert!(config.validate().is_err());
+        config.exclusions.clear();
+        config.scan_locations.custom_scan_paths = vec![PathBuf::from("relative/dir")];
+        assert!(config.validate().is_err());
+        config.scan_locations.custom_scan_paths.clear();
+
+        config.exclusions.clear();
+        config.auto_clean.schedule = AutoCleanSchedule::OnLowDiskSpace { threshold_gb: 0 };
+        assert!(config.validate().is_err());
     }
 }
@@ -1,3 +1,4 @@
+use crate::models::scan_result::SafetyLevel;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -8,11 +9,20 @@ pub struct CleaningHistory {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CleaningEntry {
+    /// Unique id; empty for entries recorded before ids were added
+    #[serde(default)]
+    pub id: String,
     pub timestamp: i64,
     pub space_reclaimed: u64,
     pub items_cleaned: u32,
     pub categories: Vec<String>,
     pub items: Vec<CleanedItem>,
+    /// Items that can still be moved back out of the Trash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restore_journal: Vec<RestoreRecord>,
+    /// Set for cleanings run by the auto-clean scheduler rather than the user
+    #[serde(default)]
+    pub automatic: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +32,135 @@ pub struct CleanedItem {
     pub category: String,
 }
 
+/// Where a cleaned item was moved to when it went to the Trash
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RestoreRecord {
+    pub original_path: PathBuf,
+    pub trashed_path: PathBuf,
+}
+
+/// Filter and pagination for listing history entries
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HistoryQuery {
+    /// Inclusive lower bound as a Unix timestamp
+    pub from: Option<i64>,
+    /// Inclusive upper bound as a Unix timestamp
+    pub to: Option<i64>,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// One page of history entries, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub entries: Vec<CleaningEntry>,
+    /// Number of entries matching the query before pagination
+    pub total: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySummary {
+    pub total_reclaimed: u64,
+    pub total_items: u64,
+    pub by_month: Vec<MonthlyReclaimed>,
+    pub by_category: Vec<CategoryReclaimed>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MonthlyReclaimed {
+    /// Calendar month as `YYYY-MM`
+    pub month: String,
+    pub space_reclaimed: u64,
+    pub items_cleaned: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CategoryReclaimed {
+    pub category: String,
+    pub space_reclaimed: u64,
+    pub items_cleaned: u64,
+}
+
+/// Whether cleaned items go to the Trash or are removed for good
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum CleanMode {
+    #[default]
+    Trash,
+    Permanent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleaningResult {
+    pub success: bool,
+    pub space_reclaimed: u64,
+    pub items_cleaned: u32,
+    pub items: Vec<CleanItemOutcome>,
+}
+
+/// Outcome for a single requested path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanItemOutcome {
+    pub path: PathBuf,
+    pub size: u64,
+    pub category: String,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Location in the Trash, when it could be determined
+    pub trashed_path: Option<PathBuf>,
+}
+
+/// What a clean would do, worked out without touching the disk
+///
+/// The user confirms a plan by its `id`; only the allowed items are then
+/// removed, and only if they have not changed since the plan was made.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanPlan {
+    pub id: String,
+    pub created_at: i64,
+    pub mode: CleanMode,
+    pub items: Vec<PlannedItem>,
+    /// Size of the allowed items
+    pub total_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedItem {
+    pub path: PathBuf,
+    pub size: u64,
+    pub category: String,
+    pub safety: SafetyLevel,
+    pub allowed: bool,
+    /// The rule that allowed or blocked the item
+    pub rule: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum RestoreStatus {
+    Restored,
+    /// Something new exists at the original path; the item stays in the Trash
+    Conflict,
+    /// The item is no longer in the Trash
+    Missing,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreItemOutcome {
+    pub path: PathBuf,
+    pub status: RestoreStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub success: bool,
+    pub items_restored: u32,
+    pub items: Vec<RestoreItemOutcome>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -29,6 +168,7 @@ mod tests {
     #[test]
     fn test_history_serialization() {
         let entry = CleaningEntry {
+            id: "1".to_string(),
             timestamp: 1625247600,
             space_reclaimed: 1024,
             items_cleaned: 1,
@@ -38,6 +178,8 @@ mod tests {
                 size: 1024,
                 category: "Cache".to_string(),
             }],
+            restore_journal: vec![],
+            automatic: false,
         };
 
         let history = CleaningHistory {
@@ -6,6 +6,13 @@ pub struct CacheScanResult {
     pub total_size: u64,
     pub categories: Vec<CacheCategory>,
     pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +61,13 @@ pub struct OrphanScanResult {
     pub total_size: u64,
     pub orphaned_apps: Vec<OrphanedApp>,
     pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +101,13 @@ pub struct LargeFileScanResult {
     pub total_size: u64,
     pub files: Vec<LargeFile>,
     pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -116,6 +137,13 @@ pub struct DuplicateScanResult {
     pub total_wasted_space: u64,
     pub groups: Vec<DuplicateGroup>,
     pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,6 +163,186 @@ pub struct DuplicateFile {
     pub is_selected: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolderScanResult {
+    /// Space held by the extra copies in identical groups; similar groups
+    /// overlap each other and are left out
+    pub total_wasted_space: u64,
+    /// Identical groups first, then similar pairs, each by wasted space
+    pub groups: Vec<DuplicateFolderGroup>,
+    pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Folders with identical trees, or a pair of folders sharing most of their contents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolderGroup {
+    /// Merkle digest shared by every folder; `None` for similar folders
+    pub digest: Option<String>,
+    /// Bytes of the larger folder also found in the other, from 0 to 1
+    pub similarity: f64,
+    /// Bytes held more than once across the group
+    pub wasted_space: u64,
+    pub folders: Vec<DuplicateFolder>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolder {
+    pub path: PathBuf,
+    pub size: u64,
+    pub files: u64,
+    /// Oldest modification time of the folder and anything inside it
+    pub modified: i64,
+    pub is_original: bool,
+    pub is_protected: bool,
+    /// Only copies in identical groups are pre-selected
+    pub is_selected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImageScanResult {
+    /// Combined size of the images other than each group's original
+    pub total_wasted_space: u64,
+    pub groups: Vec<SimilarImageGroup>,
+    pub scanned_at: i64,
+    /// Set when the scan was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Paths left out because they could not be read, usually for lack of
+    /// permission; see `permission_report`
+    #[serde(default)]
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Images that look alike, such as resized or re-encoded copies of a photo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImageGroup {
+    pub wasted_space: u64,
+    /// The original first, then by distance from it
+    pub images: Vec<SimilarImage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImage {
+    pub path: PathBuf,
+    pub size: u64,
+    pub width: u32,
+    pub height: u32,
+    pub modified: i64,
+    /// Perceptual hash as `dhash:` and 16 hex digits
+    pub hash: String,
+    /// Bits in which the hash differs from the original's
+    pub distance: u32,
+    /// The highest-resolution image in the group
+    pub is_original: bool,
+    pub is_protected: bool,
+    /// Never pre-selected, since the images are not byte-for-byte copies
+    pub is_selected: bool,
+}
+
+/// Rule for choosing which copy in a duplicate group is kept as the original
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum OriginalSelection {
+    #[default]
+    Oldest,
+    ShortestPath,
+    /// Prefer a copy inside this folder, falling back to the oldest
+    PreferredFolder(PathBuf),
+}
+
+// Disk usage breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageTree {
+    pub tree_id: String,
+    pub root: UsageNode,
+    /// Entries that could not be read and are missing from the sizes
+    pub unreadable: Vec<PathBuf>,
+    pub scanned_at: i64,
+    /// Set when the walk was cancelled before it finished
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+/// A folder, file or aggregate of small entries in a disk usage breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageNode {
+    /// Pass to `expand_usage_node` to load the children of a folder
+    pub id: String,
+    pub name: String,
+    /// `None` for an aggregate
+    pub path: Option<PathBuf>,
+    pub kind: UsageNodeKind,
+    /// Sum of file lengths
+    pub size: u64,
+    /// Bytes allocated on disk
+    pub allocated: u64,
+    pub files: u64,
+    /// Whether the node has children, loaded or not
+    pub has_children: bool,
+    /// Largest first, with entries under the minimum size gathered into
+    /// one `Other` node at the end; empty below the requested depth
+    pub children: Vec<UsageNode>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum UsageNodeKind {
+    Directory,
+    File,
+    /// Entries too small to show on their own
+    Other,
+}
+
+// Permissions
+/// A scan whose results depend on reading a location
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ScanKind {
+    Caches,
+    OrphanedApps,
+    LargeFiles,
+    Duplicates,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AccessStatus {
+    Readable,
+    Denied,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocationAccess {
+    pub path: PathBuf,
+    pub status: AccessStatus,
+    /// Scans that miss data while the location can't be read
+    pub affects: Vec<ScanKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionReport {
+    pub full_disk_access: bool,
+    pub locations: Vec<LocationAccess>,
+}
+
+/// Payload of the `scan-progress` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgress {
+    pub scan_id: String,
+    pub phase: String,
+    /// Entries visited in the current phase
+    pub current: u64,
+    /// Steps in the current phase, or 0 if unknown
+    pub total: u64,
+    pub current_path: Option<PathBuf>,
+    pub bytes_scanned: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
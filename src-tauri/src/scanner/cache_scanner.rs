@@ -0,0 +1,564 @@
+use crate::models::scan_result::{
+    CacheCategory, CacheCategoryType, CacheItem, CacheScanResult, SafetyLevel,
+};
+use crate::scanner::dir_index::DirIndex;
+use crate::scanner::progress::ScanContext;
+use crate::scanner::{age_in_days, app_name_from_bundle_id, looks_like_bundle_id, now_timestamp};
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::disk_usage_with;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Known browser cache locations, relative to the home directory
+/// (relative path, browser name, bundle identifier)
+pub(crate) const BROWSER_CACHE_PATHS: &[(&str, &str, &str)] = &[
+    (
+        "Library/Caches/com.apple.Safari",
+        "Safari",
+        "com.apple.Safari",
+    ),
+    (
+        "Library/Containers/com.apple.Safari/Data/Library/Caches",
+        "Safari",
+        "com.apple.Safari",
+    ),
+    (
+        "Library/Caches/Google/Chrome",
+        "Google Chrome",
+        "com.google.Chrome",
+    ),
+    ("Library/Caches/Firefox", "Firefox", "org.mozilla.firefox"),
+    (
+        "Library/Caches/Microsoft Edge",
+        "Microsoft Edge",
+        "com.microsoft.edgemac",
+    ),
+    (
+        "Library/Caches/BraveSoftware",
+        "Brave Browser",
+        "com.brave.Browser",
+    ),
+    (
+        "Library/Caches/com.operasoftware.Opera",
+        "Opera",
+        "com.operasoftware.Opera",
+    ),
+    ("Library/Caches/Arc", "Arc", "company.thebrowser.Browser"),
+];
+
+/// Entries of `~/Library/Caches` that belong to developer tooling
+const DEVELOPER_CACHE_NAMES: &[&str] = &[
+    "Homebrew",
+    "CocoaPods",
+    "pip",
+    "pypoetry",
+    "Yarn",
+    "go-build",
+    "JetBrains",
+    "node-gyp",
+    "ms-playwright",
+    "org.swift.swiftpm",
+    "com.apple.dt.Xcode",
+];
+
+/// Entries of `~/Library/Caches` that hold account or sync state and must not be removed
+const PROTECTED_CACHE_NAMES: &[&str] = &[
+    "CloudKit",
+    "com.apple.bird",
+    "com.apple.akd",
+    "com.apple.nsurlsessiond",
+    "FamilyCircle",
+];
+
+/// Whether an entry of `~/Library/Caches` is one of [`PROTECTED_CACHE_NAMES`],
+/// ignoring case as the default APFS volume does
+pub(crate) fn is_protected_cache_name(name: &str) -> bool {
+    PROTECTED_CACHE_NAMES
+        .iter()
+        .any(|protected| protected.eq_ignore_ascii_case(name))
+}
+
+/// Temporary files modified more recently than this may still be in use
+pub(crate) const TEMP_IN_USE_DAYS: u32 = 1;
+
+/// Scans the user's caches, logs, browser caches and temp directories
+///
+/// The home directory is injected so the scanner can be pointed at a fake
+/// home tree in tests.
+pub struct CacheScanner {
+    home: PathBuf,
+    temp_dirs: Vec<PathBuf>,
+    index: Option<Arc<DirIndex>>,
+    exclusions: ExclusionMatcher,
+}
+
+impl CacheScanner {
+    /// Create a scanner rooted at `home` with no temp directories
+    pub fn new(home: impl Into<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            temp_dirs: Vec::new(),
+            index: None,
+            exclusions: ExclusionMatcher::default(),
+        }
+    }
+
+    /// Create a scanner for the current user, including the system temp directory
+    pub fn for_current_user() -> Option<Self> {
+        let home = dirs::home_dir()?;
+        Some(Self::new(home).with_temp_dirs(vec![std::env::temp_dir()]))
+    }
+
+    /// Set the temporary directories whose contents are reported as `Temporary`
+    pub fn with_temp_dirs(mut self, temp_dirs: Vec<PathBuf>) -> Self {
+        self.temp_dirs = temp_dirs;
+        self
+    }
+
+    /// Size entries through `index`, so only directories changed since the
+    /// last scan are read again
+    pub fn with_index(mut self, index: Arc<DirIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Leave out entries the user excluded
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    pub fn scan(&self) -> CacheScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`, stopping early if it is cancelled
+    pub fn scan_with(&self, ctx: &ScanContext) -> CacheScanResult {
+        let mut items: Vec<(CacheCategoryType, CacheItem)> = Vec::new();
+
+        // Browser caches are claimed first so the generic walk below can
+        // subtract them from their parent entries instead of counting twice.
+        ctx.start_phase("caches", 0);
+        let mut claimed: Vec<(PathBuf, u64)> = Vec::new();
+        for (relative, browser, bundle_id) in BROWSER_CACHE_PATHS {
+            if ctx.is_cancelled() {
+                break;
+            }
+            let path = self.home.join(relative);
+            if let Some(item) = self.item(&path, 0, ctx) {
+                claimed.push((path, item.size));
+                items.push((
+                    CacheCategoryType::Browser,
+                    CacheItem {
+                        name: browser.to_string(),
+                        app_name: Some(browser.to_string()),
+                        bundle_id: Some(bundle_id.to_string()),
+                        safe_to_delete: SafetyLevel::Safe,
+                        description: Some(format!("{} browser cache", browser)),
+                        ..item
+                    },
+                ));
+            }
+        }
+
+        for path in list_children(&self.home.join("Library/Caches"), ctx) {
+            if ctx.is_cancelled() {
+                break;
+            }
+            if claimed.iter().any(|(c, _)| c == &path) {
+                continue;
+            }
+            let claimed_size = claimed
+                .iter()
+                .filter(|(c, _)| c.starts_with(&path))
+                .map(|(_, size)| size)
+                .sum();
+            if let Some(item) = self.item(&path, claimed_size, ctx) {
+                let category = classify_cache_entry(&item.name);
+                let safety = match category {
+                    _ if is_protected_cache_name(&item.name) => SafetyLevel::Protected,
+                    CacheCategoryType::System | CacheCategoryType::Developer => {
+                        SafetyLevel::Caution
+                    }
+                    _ => SafetyLevel::Safe,
+                };
+                items.push((
+                    category,
+                    CacheItem {
+                        safe_to_delete: safety,
+                        ..item
+                    },
+                ));
+            }
+        }
+
+        ctx.start_phase("logs", 0);
+        for path in list_children(&self.home.join("Library/Logs"), ctx) {
+            if ctx.is_cancelled() {
+                break;
+            }
+            if let Some(item) = self.item(&path, 0, ctx) {
+                items.push((
+                    CacheCategoryType::Logs,
+                    CacheItem {
+                        safe_to_delete: SafetyLevel::Safe,
+                        ..item
+                    },
+                ));
+            }
+        }
+
+        ctx.start_phase("temporary files", 0);
+        for temp_dir in &self.temp_dirs {
+            for path in list_children(temp_dir, ctx) {
+                if ctx.is_cancelled() {
+                    break;
+                }
+                if let Some(item) = self.item(&path, 0, ctx) {
+                    let safety = match item.age_days {
+                        Some(age) if age >= TEMP_IN_USE_DAYS => SafetyLevel::Safe,
+                        Some(_) => SafetyLevel::Caution,
+                        None => SafetyLevel::Unknown,
+                    };
+                    items.push((
+                        CacheCategoryType::Temporary,
+                        CacheItem {
+                            safe_to_delete: safety,
+                            ..item
+                        },
+                    ));
+                }
+            }
+        }
+
+        // Only a full scan shows which indexed entries are gone
+        if let Some(index) = self.index.as_ref().filter(|_| !ctx.is_cancelled()) {
+            let mut roots = vec![
+                self.home.join("Library/Caches"),
+                self.home.join("Library/Logs"),
+            ];
+            roots.extend(self.temp_dirs.iter().cloned());
+            index.prune_unvisited(&roots);
+        }
+
+        ctx.finish();
+        let categories = group_into_categories(items);
+        CacheScanResult {
+            total_size: categories.iter().map(|c| c.total_size).sum(),
+            categories,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+}
+
+impl CacheScanner {
+    fn item(&self, path: &Path, claimed_size: u64, ctx: &ScanContext) -> Option<CacheItem> {
+        if self.exclusions.is_excluded(path) {
+            return None;
+        }
+        build_item(path, claimed_size, self.index.as_deref(), ctx)
+    }
+}
+
+/// Classify a direct child of `~/Library/Caches` by its name
+pub fn classify_cache_entry(name: &str) -> CacheCategoryType {
+    if DEVELOPER_CACHE_NAMES.contains(&name) {
+        CacheCategoryType::Developer
+    } else if name.starts_with("com.apple.") {
+        CacheCategoryType::System
+    } else {
+        CacheCategoryType::Application
+    }
+}
+
+/// Human-readable name for a category
+pub fn category_display_name(category_type: &CacheCategoryType) -> &'static str {
+    match category_type {
+        CacheCategoryType::Browser => "Browser Caches",
+        CacheCategoryType::System => "System Caches",
+        CacheCategoryType::Application => "Application Caches",
+        CacheCategoryType::Developer => "Developer Caches",
+        CacheCategoryType::Temporary => "Temporary Files",
+        CacheCategoryType::Logs => "Logs",
+    }
+}
+
+fn list_children(dir: &Path, ctx: &ScanContext) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => {
+            log::debug!("Skipping {}: {}", dir.display(), e);
+            ctx.skip_if_denied(dir, &e);
+            Vec::new()
+        }
+    }
+}
+
+/// Build a cache item for `path`, excluding `claimed_size` bytes already
+/// reported elsewhere. Symlinks and empty entries are skipped.
+fn build_item(
+    path: &Path,
+    claimed_size: u64,
+    index: Option<&DirIndex>,
+    ctx: &ScanContext,
+) -> Option<CacheItem> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if metadata.file_type().is_symlink() {
+        return None;
+    }
+
+    let usage = match index {
+        Some(index) => index.disk_usage_with(path, ctx),
+        None => disk_usage_with(path, ctx),
+    };
+    for unreadable in &usage.unreadable {
+        ctx.skip(unreadable);
+    }
+    let size = usage.logical.saturating_sub(claimed_size);
+    if size == 0 {
+        return None;
+    }
+
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let (app_name, bundle_id) = if looks_like_bundle_id(&name) {
+        (app_name_from_bundle_id(&name), Some(name.clone()))
+    } else {
+        (Some(name.clone()), None)
+    };
+
+    Some(CacheItem {
+        path: path.to_path_buf(),
+        name,
+        size,
+        age_days: metadata.modified().ok().and_then(age_in_days),
+        app_name,
+        bundle_id,
+        safe_to_delete: SafetyLevel::Unknown,
+        description: None,
+    })
+}
+
+fn group_into_categories(items: Vec<(CacheCategoryType, CacheItem)>) -> Vec<CacheCategory> {
+    let mut categories: Vec<CacheCategory> = Vec::new();
+
+    for (category_type, item) in items {
+        let category = match categories
+            .iter_mut()
+            .position(|c| c.category_type == category_type)
+        {
+            Some(index) => &mut categories[index],
+            None => {
+                categories.push(CacheCategory {
+                    name: category_display_name(&category_type).to_string(),
+                    category_type,
+                    total_size: 0,
+                    items: Vec::new(),
+                    is_protected: false,
+                    protection_reason: None,
+                });
+                categories.last_mut().unwrap()
+            }
+        };
+        category.total_size += item.size;
+        category.items.push(item);
+    }
+
+    for category in &mut categories {
+        category.items.sort_by_key(|item| Reverse(item.size));
+    }
+    categories.sort_by_key(|c| Reverse(c.total_size));
+    categories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_filler;
+    use tempfile::tempdir;
+
+    fn find_category(result: &CacheScanResult, t: CacheCategoryType) -> &CacheCategory {
+        result
+            .categories
+            .iter()
+            .find(|c| c.category_type == t)
+            .expect("category should be present")
+    }
+
+    #[test]
+    fn test_classify_cache_entry() {
+        assert_eq!(
+            classify_cache_entry("Homebrew"),
+            CacheCategoryType::Developer
+        );
+        assert_eq!(
+            classify_cache_entry("com.apple.Music"),
+            CacheCategoryType::System
+        );
+        assert_eq!(
+            classify_cache_entry("com.spotify.client"),
+            CacheCategoryType::Application
+        );
+    }
+
+    #[test]
+    fn test_scan_fake_home() {
+        let home = tempdir().unwrap();
+        let tmp = tempdir().unwrap();
+        let caches = home.path().join("Library/Caches");
+
+        write_filler(&caches.join("com.spotify.client/data.bin"), 100);
+        write_filler(&caches.join("com.apple.Music/cache.db"), 50);
+        write_filler(&caches.join("Homebrew/downloads/pkg.tar.gz"), 200);
+        write_filler(&caches.join("com.apple.Safari/Cache.db"), 300);
+        write_filler(&caches.join("Google/Chrome/Default/Cache/data_0"), 400);
+        write_filler(&caches.join("Google/Other/blob"), 10);
+        write_filler(&caches.join("CloudKit/state"), 5);
+        write_filler(&caches.join("familycircle/state"), 5);
+        write_filler(&home.path().join("Library/Logs/MyApp/app.log"), 20);
+        write_filler(&tmp.path().join("scratch.tmp"), 30);
+
+        let scanner = CacheScanner::new(home.path()).with_temp_dirs(vec![tmp.path().into()]);
+        let result = scanner.scan();
+
+        assert_eq!(
+            result.total_size,
+            100 + 50 + 200 + 300 + 400 + 10 + 5 + 5 + 20 + 30
+        );
+        assert_eq!(result.categories.len(), 6);
+
+        let browser = find_category(&result, CacheCategoryType::Browser);
+        assert_eq!(browser.total_size, 700);
+        let chrome = browser.items.iter().find(|i| i.name == "Google Chrome");
+        assert_eq!(
+            chrome.unwrap().bundle_id.as_deref(),
+            Some("com.google.Chrome")
+        );
+
+        // Chrome is reported under Browser, only the remainder stays in "Google"
+        let apps = find_category(&result, CacheCategoryType::Application);
+        let google = apps.items.iter().find(|i| i.name == "Google").unwrap();
+        assert_eq!(google.size, 10);
+        let spotify = apps
+            .items
+            .iter()
+            .find(|i| i.name == "com.spotify.client")
+            .unwrap();
+        assert_eq!(spotify.app_name.as_deref(), Some("Spotify"));
+        assert_eq!(spotify.bundle_id.as_deref(), Some("com.spotify.client"));
+        assert_eq!(spotify.age_days, Some(0));
+        let cloudkit = apps.items.iter().find(|i| i.name == "CloudKit").unwrap();
+        assert_eq!(cloudkit.safe_to_delete, SafetyLevel::Protected);
+        let family = result
+            .categories
+            .iter()
+            .flat_map(|c| &c.items)
+            .find(|i| i.name == "familycircle")
+            .unwrap();
+        assert_eq!(family.safe_to_delete, SafetyLevel::Protected);
+
+        let system = find_category(&result, CacheCategoryType::System);
+        assert_eq!(system.items[0].safe_to_delete, SafetyLevel::Caution);
+
+        let developer = find_category(&result, CacheCategoryType::Developer);
+        assert_eq!(developer.total_size, 200);
+
+        let logs = find_category(&result, CacheCategoryType::Logs);
+        assert_eq!(logs.items[0].safe_to_delete, SafetyLevel::Safe);
+
+        // Freshly written temp files may still be in use
+        let temp = find_category(&result, CacheCategoryType::Temporary);
+        assert_eq!(temp.items[0].safe_to_delete, SafetyLevel::Caution);
+
+        // Categories are sorted by size, largest first
+        assert_eq!(
+            result.categories[0].category_type,
+            CacheCategoryType::Browser
+        );
+    }
+
+    #[test]
+    fn test_indexed_rescan_sees_cleaned_entries() {
+        let home = tempdir().unwrap();
+        let caches = home.path().join("Library/Caches");
+        write_filler(&caches.join("com.vendor.App/blob"), 100);
+        write_filler(&caches.join("com.vendor.App/old/blob"), 50);
+
+        let index = Arc::new(DirIndex::default());
+        let scanner = CacheScanner::new(home.path()).with_index(index.clone());
+        assert_eq!(scanner.scan().total_size, 150);
+        assert!(!index.is_empty());
+
+        fs::remove_dir_all(caches.join("com.vendor.App/old")).unwrap();
+        assert_eq!(scanner.scan().total_size, 100);
+
+        // Cleaning the whole entry leaves nothing of it in the index
+        fs::remove_dir_all(caches.join("com.vendor.App")).unwrap();
+        assert_eq!(scanner.scan().total_size, 0);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_excluded_entries_are_skipped() {
+        let home = tempdir().unwrap();
+        let caches = home.path().join("Library/Caches");
+        write_filler(&caches.join("com.vendor.App/blob"), 100);
+        write_filler(&caches.join("com.vendor.Keep/blob"), 50);
+
+        let result = CacheScanner::new(home.path())
+            .with_exclusions(ExclusionMatcher::new(&[PathBuf::from(
+                "**/com.vendor.Keep",
+            )]))
+            .scan();
+        assert_eq!(result.total_size, 100);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_entries_are_reported_as_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = tempdir().unwrap();
+        let caches = home.path().join("Library/Caches");
+        write_filler(&caches.join("com.vendor.App/blob"), 100);
+        let locked = caches.join("com.vendor.App/locked");
+        write_filler(&locked.join("blob"), 50);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permissions do not apply when running as root
+        let enforced = fs::read_dir(&locked).is_err();
+        let result = CacheScanner::new(home.path()).scan();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if !enforced {
+            return;
+        }
+
+        assert_eq!(result.total_size, 100);
+        assert_eq!(result.skipped, vec![locked]);
+    }
+
+    #[test]
+    fn test_cancelled_scan_is_incomplete() {
+        let home = tempdir().unwrap();
+        write_filler(&home.path().join("Library/Caches/com.vendor.App/blob"), 10);
+
+        let ctx = ScanContext::new("cancelled");
+        ctx.cancel();
+        let result = CacheScanner::new(home.path()).scan_with(&ctx);
+        assert!(result.incomplete);
+        assert!(result.categories.is_empty());
+
+        assert!(!CacheScanner::new(home.path()).scan().incomplete);
+    }
+
+    #[test]
+    fn test_scan_missing_home() {
+        let home = tempdir().unwrap();
+        let result = CacheScanner::new(home.path().join("nobody")).scan();
+        assert_eq!(result.total_size, 0);
+        assert!(result.categories.is_empty());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::scanner::progress::ScanContext;
+use crate::utils::fs::{
+    allocated_size, device_id, disk_usage_with, hardlink_id, write_atomic, DiskUsage,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DIR_INDEX_FILE_NAME: &str = "dir_index.json";
+
+/// Version of the on-disk format; an index written by any other version is discarded
+pub const DIR_INDEX_SCHEMA_VERSION: u32 = 1;
+
+/// Directories modified this close to when they were indexed are read again,
+/// since a change within the same timestamp tick would not move their mtime
+const RACY_WINDOW_NS: u64 = 2_000_000_000;
+
+/// A file with more than one link, kept apart so it is still counted only once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LinkedFile {
+    dev: u64,
+    ino: u64,
+    len: u64,
+    allocated: u64,
+}
+
+/// What a directory held the last time it was read
+///
+/// A directory's mtime changes whenever an entry is added, removed or renamed
+/// in it, so while it matches, the entry list below is still accurate. Files
+/// rewritten in place keep their old size until their directory changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DirRecord {
+    modified: u64,
+    indexed_at: u64,
+    /// The directory's own blocks plus those of its singly linked files
+    allocated: u64,
+    file_bytes: u64,
+    files: u64,
+    links: Vec<LinkedFile>,
+    subdirs: Vec<String>,
+    unreadable: Vec<PathBuf>,
+}
+
+impl DirRecord {
+    fn is_current(&self, modified: Option<u64>) -> bool {
+        modified == Some(self.modified)
+            && self.modified.saturating_add(RACY_WINDOW_NS) <= self.indexed_at
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    records: HashMap<PathBuf, DirRecord>,
+}
+
+/// Directory summaries from earlier scans, so a rescan only reads the
+/// directories whose mtime changed since
+#[derive(Default)]
+pub struct DirIndex {
+    records: Mutex<HashMap<PathBuf, DirRecord>>,
+    /// Directories measured since the last [`prune_unvisited`](Self::prune_unvisited)
+    visited: Mutex<HashSet<PathBuf>>,
+}
+
+impl DirIndex {
+    /// Number of directories indexed
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Measure `path` like [`crate::utils::fs::disk_usage`], reusing unchanged
+    /// directories and refreshing the index for everything below `path`
+    pub fn disk_usage(&self, path: &Path) -> DiskUsage {
+        self.disk_usage_with(path, &ScanContext::default())
+    }
+
+    /// [`Self::disk_usage`], reporting each directory's files to `ctx` and
+    /// stopping early once it is cancelled
+    pub fn disk_usage_with(&self, path: &Path, ctx: &ScanContext) -> DiskUsage {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => metadata,
+            _ => return disk_usage_with(path, ctx),
+        };
+
+        let walker = IndexedWalker {
+            index: self,
+            ctx,
+            device: device_id(&metadata),
+            now: nanos_since_epoch(SystemTime::now()).unwrap_or(0),
+            seen: Mutex::new(HashSet::new()),
+            visited: Mutex::new(HashMap::new()),
+        };
+        let usage = walker.dir(path, &metadata);
+
+        // Directories that were not reached again no longer exist
+        let visited = walker
+            .visited
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner());
+        self.visited
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(visited.keys().cloned());
+        let mut records = self.lock();
+        // A cancelled walk skipped directories, which keep their records
+        if !ctx.is_cancelled() {
+            records.retain(|p, _| !p.starts_with(path));
+        }
+        records.extend(visited);
+        usage
+    }
+
+    /// Drop records below `roots` for directories not measured since the last
+    /// prune, such as cache folders cleaned away since the previous scan
+    ///
+    /// Call once every entry under `roots` has been measured.
+    pub fn prune_unvisited(&self, roots: &[PathBuf]) {
+        let visited = std::mem::take(&mut *self.visited.lock().unwrap_or_else(|e| e.into_inner()));
+        self.lock()
+            .retain(|p, _| visited.contains(p) || !roots.iter().any(|root| p.starts_with(root)));
+    }
+
+    fn lookup(&self, path: &Path) -> Option<DirRecord> {
+        self.lock().get(path).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, DirRecord>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct IndexedWalker<'a> {
+    index: &'a DirIndex,
+    ctx: &'a ScanContext,
+    device: Option<u64>,
+    now: u64,
+    seen: Mutex<HashSet<(u64, u64)>>,
+    visited: Mutex<HashMap<PathBuf, DirRecord>>,
+}
+
+impl IndexedWalker<'_> {
+    fn dir(&self, path: &Path, metadata: &Metadata) -> DiskUsage {
+        if device_id(metadata) != self.device || self.ctx.is_cancelled() {
+            return DiskUsage::default();
+        }
+        let modified = metadata.modified().ok().and_then(nanos_since_epoch);
+        let record = match self.index.lookup(path).filter(|r| r.is_current(modified)) {
+            Some(record) => record,
+            None => match self.read(path, metadata, modified) {
+                Some(record) => record,
+                // Unreadable directories are not indexed so they are retried next time
+                None => {
+                    return DiskUsage {
+                        allocated: allocated_size(metadata),
+                        ..DiskUsage::unreadable(path)
+                    }
+                }
+            },
+        };
+
+        let mut usage = DiskUsage {
+            logical: record.file_bytes,
+            allocated: record.allocated,
+            files: record.files,
+            unreadable: record.unreadable.clone(),
+        };
+        {
+            let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+            for link in &record.links {
+                if seen.insert((link.dev, link.ino)) {
+                    usage.logical += link.len;
+                    usage.allocated += link.allocated;
+                    usage.files += 1;
+                }
+            }
+        }
+        self.ctx.visit(path, usage.logical);
+
+        let subdirs = record
+            .subdirs
+            .par_iter()
+            .map(|name| {
+                let child = path.join(name);
+                match fs::symlink_metadata(&child) {
+                    Ok(metadata) if metadata.is_dir() => self.dir(&child, &metadata),
+                    Ok(_) => DiskUsage::default(),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => DiskUsage::default(),
+                    Err(_) => DiskUsage::unreadable(&child),
+                }
+            })
+            .reduce(DiskUsage::default, DiskUsage::merge);
+        let usage = usage.merge(subdirs);
+
+        self.visited
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path.to_path_buf(), record);
+        usage
+    }
+
+    /// Read a directory's entries into a fresh record
+    fn read(&self, path: &Path, metadata: &Metadata, modified: Option<u64>) -> Option<DirRecord> {
+        let entries = fs::read_dir(path).ok()?;
+        let mut record = DirRecord {
+            // Without an mtime the record never counts as current
+            modified: modified.unwrap_or(u64::MAX),
+            indexed_at: self.now,
+            allocated: allocated_size(metadata),
+            file_bytes: 0,
+            files: 0,
+            links: Vec::new(),
+            subdirs: Vec::new(),
+            unreadable: Vec::new(),
+        };
+
+        for entry in entries {
+            let Ok(entry) = entry else {
+                record.unreadable.push(path.to_path_buf());
+                continue;
+            };
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    record.unreadable.push(entry.path());
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                record
+                    .subdirs
+                    .push(entry.file_name().to_string_lossy().to_string());
+            } else if let Some((dev, ino)) = hardlink_id(&metadata) {
+                record.links.push(LinkedFile {
+                    dev,
+                    ino,
+                    len: metadata.len(),
+                    allocated: allocated_size(&metadata),
+                });
+            } else {
+                record.file_bytes += metadata.len();
+                record.allocated += allocated_size(&metadata);
+                record.files += 1;
+            }
+        }
+        Some(record)
+    }
+}
+
+pub(crate) fn nanos_since_epoch(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|d| u64::try_from(d.as_nanos()).ok())
+}
+
+/// JSON file holding the directory index
+pub struct DirIndexStore {
+    path: PathBuf,
+}
+
+impl DirIndexStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store kept in the given app data directory
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::new(dir.join(DIR_INDEX_FILE_NAME))
+    }
+
+    /// Load the index, starting empty if the file is missing, unreadable as
+    /// JSON or from another schema version
+    pub fn load(&self) -> io::Result<DirIndex> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(DirIndex::default()),
+            Err(e) => return Err(e),
+        };
+
+        match serde_json::from_slice::<IndexFile>(&bytes) {
+            Ok(file) if file.version == DIR_INDEX_SCHEMA_VERSION => Ok(DirIndex {
+                records: Mutex::new(file.records),
+                visited: Mutex::default(),
+            }),
+            Ok(file) => {
+                log::debug!("Discarding directory index version {}", file.version);
+                Ok(DirIndex::default())
+            }
+            Err(e) => {
+                log::debug!("Discarding unreadable directory index: {}", e);
+                Ok(DirIndex::default())
+            }
+        }
+    }
+
+    pub fn save(&self, index: &DirIndex) -> io::Result<()> {
+        let file = IndexFile {
+            version: DIR_INDEX_SCHEMA_VERSION,
+            records: index.lock().clone(),
+        };
+        let json = serde_json::to_vec(&file)?;
+        write_atomic(&self.path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::fs::disk_usage;
+    use std::fs::File;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    /// Backdate a directory so its record falls outside the racy window
+    fn backdate(path: &Path) -> SystemTime {
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        File::open(path).unwrap().set_modified(modified).unwrap();
+        modified
+    }
+
+    fn build_tree(root: &Path) {
+        fs::create_dir_all(root.join("a/deep")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("top.bin"), vec![0u8; 100]).unwrap();
+        fs::write(root.join("a/deep/file.bin"), vec![0u8; 200]).unwrap();
+        fs::write(root.join("b/file.bin"), vec![0u8; 300]).unwrap();
+        for dir in ["a/deep", "a", "b", ""] {
+            backdate(&root.join(dir));
+        }
+    }
+
+    #[test]
+    fn test_rescan_picks_up_changes() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+        let index = DirIndex::default();
+
+        assert_eq!(index.disk_usage(dir.path()).logical, 600);
+        assert_eq!(index.len(), 4);
+
+        // Adding a file deep down only touches that directory's mtime
+        fs::write(dir.path().join("a/deep/new.bin"), vec![0u8; 50]).unwrap();
+        assert_eq!(index.disk_usage(dir.path()).logical, 650);
+
+        fs::remove_dir_all(dir.path().join("b")).unwrap();
+        let usage = index.disk_usage(dir.path());
+        assert_eq!(usage.logical, 350);
+        assert_eq!(usage.files, 3);
+        assert_eq!(index.len(), 3);
+        assert_eq!(usage.logical, disk_usage(dir.path()).logical);
+
+        let ctx = ScanContext::default();
+        ctx.cancel();
+        assert_eq!(index.disk_usage_with(dir.path(), &ctx).logical, 0);
+        assert_eq!(index.len(), 3);
+    }
+
+    #[test]
+    fn test_unchanged_directories_are_not_read_again() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+        let index = DirIndex::default();
+        assert_eq!(index.disk_usage(dir.path()).logical, 600);
+
+        // Sneak a file in and restore the mtime: the stale record is trusted
+        let b = dir.path().join("b");
+        let modified = fs::metadata(&b).unwrap().modified().unwrap();
+        fs::write(b.join("hidden.bin"), vec![0u8; 1000]).unwrap();
+        File::open(&b).unwrap().set_modified(modified).unwrap();
+        assert_eq!(index.disk_usage(dir.path()).logical, 600);
+
+        // Measuring a subtree refreshes only that part of the index
+        backdate(&b);
+        assert_eq!(index.disk_usage(&b).logical, 1300);
+        assert_eq!(index.disk_usage(dir.path()).logical, 1600);
+    }
+
+    #[test]
+    fn test_prune_drops_entries_not_measured_again() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+        let index = DirIndex::default();
+        let roots = [dir.path().to_path_buf()];
+
+        // Measured one entry at a time, as the cache scanner does
+        index.disk_usage(&dir.path().join("a"));
+        index.disk_usage(&dir.path().join("b"));
+        index.prune_unvisited(&roots);
+        assert_eq!(index.len(), 3);
+
+        fs::remove_dir_all(dir.path().join("b")).unwrap();
+        index.disk_usage(&dir.path().join("a"));
+        assert_eq!(index.len(), 3);
+        index.prune_unvisited(&roots);
+        assert_eq!(index.len(), 2);
+        assert!(index.lookup(&dir.path().join("b")).is_none());
+
+        // Records outside the roots are left alone
+        index.prune_unvisited(&[dir.path().join("b")]);
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_recently_modified_directories_are_always_read() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("one.bin"), vec![0u8; 10]).unwrap();
+        let index = DirIndex::default();
+        assert_eq!(index.disk_usage(dir.path()).logical, 10);
+
+        fs::write(dir.path().join("two.bin"), vec![0u8; 10]).unwrap();
+        assert_eq!(index.disk_usage(dir.path()).logical, 20);
+    }
+
+    #[test]
+    fn test_store_round_trip_and_version_mismatch() {
+        let data = tempdir().unwrap();
+        let tree = tempdir().unwrap();
+        build_tree(tree.path());
+        let store = DirIndexStore::in_dir(data.path());
+        assert!(store.load().unwrap().is_empty());
+
+        let index = DirIndex::default();
+        index.disk_usage(tree.path());
+        store.save(&index).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded.disk_usage(tree.path()).logical, 600);
+
+        let stale = serde_json::json!({ "version": DIR_INDEX_SCHEMA_VERSION + 1, "records": {} });
+        fs::write(data.path().join(DIR_INDEX_FILE_NAME), stale.to_string()).unwrap();
+        assert!(store.load().unwrap().is_empty());
+        fs::write(data.path().join(DIR_INDEX_FILE_NAME), "not json").unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+}
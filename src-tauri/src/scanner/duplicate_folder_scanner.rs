@@ -0,0 +1,782 @@
+use crate::models::config::HashAlgorithm;
+use crate::models::scan_result::{
+    DuplicateFolder, DuplicateFolderGroup, DuplicateFolderScanResult, OriginalSelection,
+};
+use crate::scanner::duplicate_scanner::{confirm_duplicates, pick_original_among, Candidate};
+use crate::scanner::hash_cache::HashCache;
+use crate::scanner::now_timestamp;
+use crate::scanner::progress::ScanContext;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::hardlink_id;
+use crate::utils::hash::{hash_bytes, merkle_digest};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Share of the larger folder's bytes that must also be in the other folder
+/// for the pair to be reported as similar
+pub const SIMILAR_FOLDER_THRESHOLD: f64 = 0.9;
+
+/// Contents found in more folders than this don't pair folders up for the
+/// similarity check; they are usually boilerplate such as license files
+const MAX_SEED_FOLDERS: usize = 16;
+
+/// Finds folders whose whole trees are identical, and pairs of folders that
+/// share most of their contents
+///
+/// File hashes come from the same staged pipeline as [`DuplicateScanner`];
+/// each folder then gets a Merkle digest of its children's names and hashes.
+///
+/// [`DuplicateScanner`]: crate::scanner::duplicate_scanner::DuplicateScanner
+pub struct DuplicateFolderScanner {
+    roots: Vec<PathBuf>,
+    exclusions: ExclusionMatcher,
+    min_size: u64,
+    threshold: f64,
+    selection: OriginalSelection,
+    algorithm: HashAlgorithm,
+    hash_cache: Option<Arc<HashCache>>,
+}
+
+struct Folder {
+    path: PathBuf,
+    parent: Option<usize>,
+    children: Vec<Child>,
+    /// Set when part of the folder could not be listed, so its contents are unknown
+    unreadable: bool,
+    size: u64,
+    files: u64,
+    modified: i64,
+    /// `None` when a file inside has no copy anywhere, so no other folder can match
+    digest: Option<String>,
+}
+
+/// Everything below the roots, with the files that need a hash
+struct Listing {
+    folders: Vec<Folder>,
+    candidates: Vec<Candidate>,
+    /// Folder and child index of each candidate
+    locations: HashMap<PathBuf, (usize, usize)>,
+}
+
+struct Child {
+    name: OsString,
+    kind: ChildKind,
+}
+
+enum ChildKind {
+    File {
+        size: u64,
+        modified: i64,
+        /// Index into the hashes of files seen more than once
+        content: Option<u32>,
+    },
+    Folder(usize),
+    Symlink(String),
+}
+
+impl DuplicateFolderScanner {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            exclusions: ExclusionMatcher::default(),
+            min_size: 1,
+            threshold: SIMILAR_FOLDER_THRESHOLD,
+            selection: OriginalSelection::default(),
+            algorithm: HashAlgorithm::default(),
+            hash_cache: None,
+        }
+    }
+
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    /// Ignore folders holding less than `min_size` bytes (empty folders are always ignored)
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size.max(1);
+        self
+    }
+
+    /// Report folder pairs sharing at least this share of their bytes, from 0 to 1
+    pub fn with_similarity_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_selection(mut self, selection: OriginalSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Compare file contents with `algorithm`
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Reuse hashes of unchanged files from earlier scans, and record new ones
+    pub fn with_hash_cache(mut self, cache: Arc<HashCache>) -> Self {
+        self.hash_cache = Some(cache);
+        self
+    }
+
+    pub fn scan(&self) -> DuplicateFolderScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`
+    ///
+    /// A walk cancelled halfway leaves folders partly listed, so it reports
+    /// nothing. Files not hashed before a later cancellation count as unique,
+    /// which can only hide matches.
+    pub fn scan_with(&self, ctx: &ScanContext) -> DuplicateFolderScanResult {
+        ctx.start_phase("walking", 0);
+        let Listing {
+            mut folders,
+            candidates,
+            locations,
+        } = self.walk(ctx);
+
+        let mut groups = Vec::new();
+        if !ctx.is_cancelled() {
+            // Empty files take part without being read
+            let mut hashes = vec![hash_bytes(&[], self.algorithm)];
+            let confirmed =
+                confirm_duplicates(candidates, self.algorithm, self.hash_cache.as_deref(), ctx);
+            for (hash, files) in confirmed {
+                let id = hashes.len() as u32;
+                hashes.push(hash);
+                for file in files {
+                    let (folder, child) = locations[&file.path];
+                    if let ChildKind::File { content, .. } =
+                        &mut folders[folder].children[child].kind
+                    {
+                        *content = Some(id);
+                    }
+                }
+            }
+
+            ctx.start_phase("comparing folders", 0);
+            summarize(&mut folders, &hashes);
+            groups = self.identical_groups(&folders);
+            groups.extend(self.similar_groups(&folders, ctx));
+        }
+        ctx.finish();
+
+        DuplicateFolderScanResult {
+            total_wasted_space: groups
+                .iter()
+                .filter(|g| g.digest.is_some())
+                .map(|g| g.wasted_space)
+                .sum(),
+            groups,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+
+    /// Roots with the ones nested in another root dropped, so nothing is walked twice
+    fn distinct_roots(&self) -> Vec<&PathBuf> {
+        let mut roots: Vec<&PathBuf> = self.roots.iter().collect();
+        roots.sort();
+        roots.dedup();
+        let mut distinct: Vec<&PathBuf> = Vec::new();
+        for root in roots {
+            if !distinct.iter().any(|r| root.starts_with(r)) {
+                distinct.push(root);
+            }
+        }
+        distinct
+    }
+
+    /// List every folder below the roots
+    fn walk(&self, ctx: &ScanContext) -> Listing {
+        let mut folders: Vec<Folder> = Vec::new();
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+        let mut candidates = Vec::new();
+        let mut locations = HashMap::new();
+        let mut seen_links = HashSet::new();
+
+        'roots: for root in self.distinct_roots() {
+            let walker = WalkDir::new(root)
+                .follow_links(false)
+                .into_iter()
+                .filter_entry(|e| !self.exclusions.is_excluded(e.path()));
+
+            for entry in walker {
+                if ctx.is_cancelled() {
+                    break 'roots;
+                }
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        if let (Some(path), Some(error)) = (e.path(), e.io_error()) {
+                            ctx.skip_if_denied(path, error);
+                        }
+                        // A folder that can't be listed, or one holding an unreadable entry
+                        let folder = e.path().and_then(|path| {
+                            index
+                                .get(path)
+                                .or_else(|| path.parent().and_then(|p| index.get(p)))
+                        });
+                        if let Some(&folder) = folder {
+                            folders[folder].unreadable = true;
+                        }
+                        continue;
+                    }
+                };
+
+                let parent = if entry.depth() == 0 {
+                    None
+                } else {
+                    entry.path().parent().and_then(|p| index.get(p)).copied()
+                };
+                let file_type = entry.file_type();
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => {
+                        if let Some(parent) = parent {
+                            folders[parent].unreadable = true;
+                        }
+                        continue;
+                    }
+                };
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or_default();
+
+                if file_type.is_dir() {
+                    let id = folders.len();
+                    if let Some(parent) = parent {
+                        folders[parent].children.push(Child {
+                            name: entry.file_name().to_os_string(),
+                            kind: ChildKind::Folder(id),
+                        });
+                    }
+                    index.insert(entry.path().to_path_buf(), id);
+                    folders.push(Folder {
+                        path: entry.into_path(),
+                        parent,
+                        children: Vec::new(),
+                        unreadable: false,
+                        size: 0,
+                        files: 0,
+                        modified,
+                        digest: None,
+                    });
+                    continue;
+                }
+
+                let Some(parent) = parent else {
+                    continue;
+                };
+                let kind = if file_type.is_symlink() {
+                    match fs::read_link(entry.path()) {
+                        Ok(target) => ChildKind::Symlink(target.to_string_lossy().to_string()),
+                        Err(_) => {
+                            folders[parent].unreadable = true;
+                            continue;
+                        }
+                    }
+                } else if file_type.is_file() {
+                    let size = metadata.len();
+                    ctx.visit(entry.path(), size);
+                    // Another link to a file already listed frees nothing when
+                    // deleted, so it stays unhashed and its folders never match
+                    let linked = hardlink_id(&metadata).is_some_and(|id| !seen_links.insert(id));
+                    if size > 0 && !linked {
+                        locations.insert(
+                            entry.path().to_path_buf(),
+                            (parent, folders[parent].children.len()),
+                        );
+                        candidates.push(Candidate {
+                            path: entry.path().to_path_buf(),
+                            size,
+                            modified,
+                        });
+                    }
+                    ChildKind::File {
+                        size,
+                        modified,
+                        content: (size == 0).then_some(0),
+                    }
+                } else {
+                    // Sockets and pipes hold no data
+                    continue;
+                };
+                folders[parent].children.push(Child {
+                    name: entry.file_name().to_os_string(),
+                    kind,
+                });
+            }
+        }
+
+        Listing {
+            folders,
+            candidates,
+            locations,
+        }
+    }
+
+    fn identical_groups(&self, folders: &[Folder]) -> Vec<DuplicateFolderGroup> {
+        let mut by_digest: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (id, folder) in folders.iter().enumerate() {
+            if let Some(digest) = folder.digest.as_deref() {
+                if folder.size >= self.min_size {
+                    by_digest.entry(digest).or_default().push(id);
+                }
+            }
+        }
+        by_digest.retain(|_, members| members.len() > 1);
+
+        // Copies inside copied folders are already covered by their parents' group
+        let grouped: HashSet<&str> = by_digest.keys().copied().collect();
+        let covered = |id: usize| {
+            folders[id]
+                .parent
+                .and_then(|p| folders[p].digest.as_deref())
+                .is_some_and(|digest| grouped.contains(digest))
+        };
+
+        let mut groups: Vec<DuplicateFolderGroup> = by_digest
+            .into_iter()
+            .filter(|(_, members)| !members.iter().all(|&id| covered(id)))
+            .map(|(digest, members)| {
+                let size = folders[members[0]].size;
+                let uncovered = members.iter().filter(|&&id| !covered(id)).count() as u64;
+                let mut group = self.build_group(folders, &members, |id| !covered(id));
+                group.digest = Some(digest.to_string());
+                group.similarity = 1.0;
+                // Covered copies are counted, and pre-selected, with their parents
+                group.wasted_space = if uncovered < members.len() as u64 {
+                    size * uncovered
+                } else {
+                    size * (uncovered - 1)
+                };
+                group
+            })
+            .collect();
+        groups.sort_by_key(|g| Reverse(g.wasted_space));
+        groups
+    }
+
+    /// Pairs of folders sharing at least `threshold` of their bytes without
+    /// being identical
+    ///
+    /// Pairs start from folders holding copies of the same file and climb to
+    /// their parents while they stay similar, so only folders with copies in
+    /// them are ever compared.
+    fn similar_groups(&self, folders: &[Folder], ctx: &ScanContext) -> Vec<DuplicateFolderGroup> {
+        let mut holders: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (id, folder) in folders.iter().enumerate() {
+            for child in &folder.children {
+                if let ChildKind::File {
+                    content: Some(content),
+                    ..
+                } = child.kind
+                {
+                    let ids = holders.entry(content).or_default();
+                    if ids.last() != Some(&id) {
+                        ids.push(id);
+                    }
+                }
+            }
+        }
+
+        let mut queue = VecDeque::new();
+        for ids in holders.values().filter(|ids| ids.len() <= MAX_SEED_FOLDERS) {
+            for (i, &a) in ids.iter().enumerate() {
+                queue.extend(ids[i + 1..].iter().map(|&b| (a, b)));
+            }
+        }
+
+        let mut contents: HashMap<usize, Vec<(u32, u64)>> = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut similar: HashMap<(usize, usize), (f64, u64)> = HashMap::new();
+        while let Some((a, b)) = queue.pop_front() {
+            if ctx.is_cancelled() {
+                break;
+            }
+            let pair = (a.min(b), a.max(b));
+            if a == b || !visited.insert(pair) {
+                continue;
+            }
+            let (first, second) = (&folders[pair.0], &folders[pair.1]);
+            if first.path.starts_with(&second.path) || second.path.starts_with(&first.path) {
+                continue;
+            }
+            let larger = first.size.max(second.size);
+            let smaller = first.size.min(second.size);
+            // The shared bytes can't exceed the smaller folder
+            if smaller < self.min_size || (smaller as f64) < self.threshold * larger as f64 {
+                continue;
+            }
+
+            for id in [pair.0, pair.1] {
+                contents
+                    .entry(id)
+                    .or_insert_with(|| descendant_contents(folders, id));
+            }
+            let shared = shared_bytes(&contents[&pair.0], &contents[&pair.1]);
+            let similarity = shared as f64 / larger as f64;
+            if similarity < self.threshold {
+                continue;
+            }
+            similar.insert(pair, (similarity, shared));
+            if let (Some(a), Some(b)) = (first.parent, second.parent) {
+                queue.push_back((a, b));
+            }
+        }
+
+        let mut groups: Vec<DuplicateFolderGroup> = similar
+            .iter()
+            .filter(|(&(a, b), _)| {
+                let identical =
+                    folders[a].digest.is_some() && folders[a].digest == folders[b].digest;
+                // Report the outermost similar pair only
+                let parents_similar = match (folders[a].parent, folders[b].parent) {
+                    (Some(pa), Some(pb)) => similar.contains_key(&(pa.min(pb), pa.max(pb))),
+                    _ => false,
+                };
+                !identical && !parents_similar
+            })
+            .map(|(&(a, b), &(similarity, shared))| {
+                // Similar folders each hold something the other lacks
+                let mut group = self.build_group(folders, &[a, b], |_| false);
+                group.similarity = similarity;
+                group.wasted_space = shared;
+                group
+            })
+            .collect();
+        groups.sort_by(|a, b| {
+            b.wasted_space
+                .cmp(&a.wasted_space)
+                .then_with(|| a.folders[0].path.cmp(&b.folders[0].path))
+        });
+        groups
+    }
+
+    /// Group `members` with the original picked, pre-selecting the other
+    /// copies for which `preselect` holds
+    fn build_group(
+        &self,
+        folders: &[Folder],
+        members: &[usize],
+        preselect: impl Fn(usize) -> bool,
+    ) -> DuplicateFolderGroup {
+        let mut members = members.to_vec();
+        members.sort_by(|&a, &b| folders[a].path.cmp(&folders[b].path));
+        let original = pick_original_among(
+            members
+                .iter()
+                .map(|&id| (folders[id].path.as_path(), folders[id].modified)),
+            &self.selection,
+        );
+
+        let folders = members
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| DuplicateFolder {
+                path: folders[id].path.clone(),
+                size: folders[id].size,
+                files: folders[id].files,
+                modified: folders[id].modified,
+                is_original: index == original,
+                is_protected: false,
+                is_selected: index != original && preselect(id),
+            })
+            .collect();
+
+        DuplicateFolderGroup {
+            digest: None,
+            similarity: 0.0,
+            wasted_space: 0,
+            folders,
+        }
+    }
+}
+
+/// Fill in each folder's size, file count, oldest modification and digest,
+/// children first
+fn summarize(folders: &mut [Folder], hashes: &[String]) {
+    // Folders are listed before their contents, so children have higher indexes
+    for id in (0..folders.len()).rev() {
+        let folder = &folders[id];
+        let (mut size, mut files, mut modified) = (0, 0, folder.modified);
+        let mut entries: Vec<(&OsStr, &str)> = Vec::new();
+        let mut complete = !folder.unreadable;
+
+        for child in &folder.children {
+            let hash = match &child.kind {
+                ChildKind::File {
+                    size: file_size,
+                    modified: file_modified,
+                    content,
+                } => {
+                    size += file_size;
+                    files += 1;
+                    modified = modified.min(*file_modified);
+                    content.map(|c| hashes[c as usize].as_str())
+                }
+                ChildKind::Folder(child_id) => {
+                    let child_folder = &folders[*child_id];
+                    size += child_folder.size;
+                    files += child_folder.files;
+                    modified = modified.min(child_folder.modified);
+                    child_folder.digest.as_deref()
+                }
+                ChildKind::Symlink(target) => Some(target.as_str()),
+            };
+            match hash {
+                Some(hash) => entries.push((&child.name, hash)),
+                None => complete = false,
+            }
+        }
+
+        let digest = complete.then(|| merkle_digest(entries));
+        let folder = &mut folders[id];
+        folder.size = size;
+        folder.files = files;
+        folder.modified = modified;
+        folder.digest = digest;
+    }
+}
+
+/// Contents of every hashed file below the folder, sorted by content
+fn descendant_contents(folders: &[Folder], id: usize) -> Vec<(u32, u64)> {
+    let mut contents = Vec::new();
+    let mut pending = vec![id];
+    while let Some(id) = pending.pop() {
+        for child in &folders[id].children {
+            match child.kind {
+                ChildKind::File {
+                    size,
+                    content: Some(content),
+                    ..
+                } => contents.push((content, size)),
+                ChildKind::Folder(child_id) => pending.push(child_id),
+                _ => {}
+            }
+        }
+    }
+    contents.sort_unstable();
+    contents
+}
+
+/// Bytes in both sorted content lists, counting each copy at most once
+fn shared_bytes(a: &[(u32, u64)], b: &[(u32, u64)]) -> u64 {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].0.cmp(&b[j].0) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                shared += a[i].1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    shared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_file;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write_project(dir: &Path) {
+        write_file(&dir.join("README.md"), b"# Project");
+        write_file(&dir.join("src/main.rs"), &[1u8; 3000]);
+        write_file(&dir.join("src/lib.rs"), &[2u8; 5000]);
+        write_file(&dir.join("assets/logo.png"), &[3u8; 10_000]);
+        write_file(&dir.join(".keep"), b"");
+    }
+
+    fn paths(group: &DuplicateFolderGroup) -> Vec<&Path> {
+        group.folders.iter().map(|f| f.path.as_path()).collect()
+    }
+
+    #[test]
+    fn test_identical_trees_are_grouped_at_the_top() {
+        let root = tempdir().unwrap();
+        let project = root.path().join("project");
+        write_project(&project);
+        write_project(&root.path().join("project copy"));
+        write_project(&root.path().join("project old"));
+        // Same contents under another name is not an identical tree
+        let renamed = root.path().join("renamed");
+        write_project(&renamed);
+        fs::rename(renamed.join("README.md"), renamed.join("README.txt")).unwrap();
+
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()])
+            .with_selection(OriginalSelection::PreferredFolder(project.clone()))
+            .scan();
+        let identical: Vec<_> = result
+            .groups
+            .iter()
+            .filter(|g| g.digest.is_some())
+            .collect();
+        assert_eq!(identical.len(), 3);
+
+        let group = identical[0];
+        assert_eq!(
+            paths(group),
+            vec![
+                project.as_path(),
+                root.path().join("project copy").as_path(),
+                root.path().join("project old").as_path(),
+            ]
+        );
+        let size = 9 + 3000 + 5000 + 10_000;
+        assert_eq!(group.folders[0].size, size);
+        assert_eq!(group.folders[0].files, 5);
+        assert_eq!(group.wasted_space, 2 * size);
+        assert!(group.folders[0].is_original);
+        assert!(group.folders.iter().all(|f| f.is_selected != f.is_original));
+
+        // Only the renamed project's copy of src/ is not covered by the group above
+        let src = identical
+            .iter()
+            .find(|g| g.folders[0].path.ends_with("src"))
+            .unwrap();
+        assert_eq!(src.folders.len(), 4);
+        assert_eq!(src.wasted_space, 8000);
+        let selected: Vec<_> = src.folders.iter().filter(|f| f.is_selected).collect();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].path, renamed.join("src"));
+        assert_eq!(result.total_wasted_space, 2 * size + 8000 + 10_000);
+
+        // The renamed project shares every byte but not its layout
+        let similar: Vec<_> = result
+            .groups
+            .iter()
+            .filter(|g| g.digest.is_none())
+            .collect();
+        assert_eq!(similar.len(), 3);
+        for group in similar {
+            assert!(group.folders.iter().any(|f| f.path == renamed));
+            assert_eq!(group.similarity, 1.0);
+            assert_eq!(group.wasted_space, size);
+            assert!(group.folders.iter().all(|f| !f.is_selected));
+        }
+    }
+
+    #[test]
+    fn test_mostly_shared_folders_are_similar() {
+        let root = tempdir().unwrap();
+        let photos = root.path().join("Photos/2023");
+        let export = root.path().join("Export/2023");
+        for i in 0..10u8 {
+            write_file(&photos.join(format!("IMG_{}.jpg", i)), &[i; 1000]);
+            write_file(&export.join(format!("IMG_{}.jpg", i)), &[i; 1000]);
+        }
+        // 10% extra in one folder stays above the threshold
+        write_file(&photos.join("IMG_extra.jpg"), &[42u8; 1000]);
+        let mut half = vec![0u8; 5000];
+        half[0] = 1;
+        write_file(&root.path().join("unrelated/a.bin"), &half);
+        write_file(&root.path().join("unrelated/b.bin"), &[0u8; 1000]);
+
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()]).scan();
+        assert!(result.groups.iter().all(|g| g.digest.is_none()));
+        assert_eq!(result.total_wasted_space, 0);
+
+        // The pair climbs to Photos/Export, which share the same bytes
+        assert_eq!(result.groups.len(), 1);
+        let group = &result.groups[0];
+        assert_eq!(
+            paths(group),
+            vec![root.path().join("Export"), root.path().join("Photos")]
+        );
+        assert_eq!(group.wasted_space, 10_000);
+        assert!((group.similarity - 10.0 / 11.0).abs() < 1e-9);
+        assert_eq!(group.folders.iter().filter(|f| f.is_original).count(), 1);
+
+        let strict = DuplicateFolderScanner::new(vec![root.path().to_path_buf()])
+            .with_similarity_threshold(0.95)
+            .scan();
+        assert!(strict.groups.is_empty());
+    }
+
+    #[test]
+    fn test_unique_contents_break_identity() {
+        let root = tempdir().unwrap();
+        write_project(&root.path().join("a"));
+        write_project(&root.path().join("b"));
+        write_file(&root.path().join("b/src/new.rs"), b"only here");
+
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()])
+            .with_similarity_threshold(1.0)
+            .scan();
+        // Only the untouched assets folders remain identical
+        let identical: Vec<_> = result
+            .groups
+            .iter()
+            .filter(|g| g.digest.is_some())
+            .collect();
+        assert_eq!(identical.len(), 1);
+        assert_eq!(
+            paths(identical[0]),
+            vec![root.path().join("a/assets"), root.path().join("b/assets")]
+        );
+
+        let ctx = ScanContext::new("cancelled");
+        ctx.cancel();
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()]).scan_with(&ctx);
+        assert!(result.incomplete);
+        assert!(result.groups.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinked_trees_are_not_copies() {
+        let root = tempdir().unwrap();
+        let project = root.path().join("project");
+        write_project(&project);
+        let linked = root.path().join("linked");
+        for entry in WalkDir::new(&project) {
+            let entry = entry.unwrap();
+            let target = linked.join(entry.path().strip_prefix(&project).unwrap());
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target).unwrap();
+            } else {
+                fs::hard_link(entry.path(), &target).unwrap();
+            }
+        }
+
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()]).scan();
+        assert!(result.groups.is_empty());
+        assert_eq!(result.total_wasted_space, 0);
+
+        // A real copy still pairs with whichever of the linked trees was listed first
+        let copy = root.path().join("copy");
+        write_project(&copy);
+        let result = DuplicateFolderScanner::new(vec![root.path().to_path_buf()]).scan();
+        let top = &result.groups[0];
+        assert_eq!(top.folders.len(), 2);
+        assert!(top.folders.iter().any(|f| f.path == copy));
+        assert_eq!(top.wasted_space, 9 + 3000 + 5000 + 10_000);
+    }
+
+    #[test]
+    fn test_shared_bytes_counts_each_copy_once() {
+        let a = [(1, 10), (1, 10), (2, 5), (4, 7)];
+        let b = [(1, 10), (2, 5), (2, 5), (3, 9)];
+        assert_eq!(shared_bytes(&a, &b), 15);
+        assert_eq!(shared_bytes(&a, &[]), 0);
+    }
+}
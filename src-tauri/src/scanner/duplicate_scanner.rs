@@ -0,0 +1,495 @@
+use crate::models::config::HashAlgorithm;
+use crate::models::scan_result::{
+    DuplicateFile, DuplicateGroup, DuplicateScanResult, OriginalSelection,
+};
+use crate::scanner::hash_cache::HashCache;
+use crate::scanner::now_timestamp;
+use crate::scanner::progress::ScanContext;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::hash::{hash_file, hash_file_partial};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Files up to this size are fully covered by the partial hash (first 4KB + last 4KB)
+const PARTIAL_HASH_COVERS_WHOLE_FILE: u64 = 8192;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Candidate {
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    pub(crate) modified: i64,
+}
+
+/// Finds identical files by narrowing candidates by size, then partial hash,
+/// then full hash
+pub struct DuplicateScanner {
+    roots: Vec<PathBuf>,
+    exclusions: ExclusionMatcher,
+    min_size: u64,
+    selection: OriginalSelection,
+    algorithm: HashAlgorithm,
+    hash_cache: Option<Arc<HashCache>>,
+}
+
+impl DuplicateScanner {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            exclusions: ExclusionMatcher::default(),
+            min_size: 1,
+            selection: OriginalSelection::default(),
+            algorithm: HashAlgorithm::default(),
+            hash_cache: None,
+        }
+    }
+
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    /// Ignore files smaller than `min_size` bytes (empty files are always ignored)
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size.max(1);
+        self
+    }
+
+    pub fn with_selection(mut self, selection: OriginalSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Compare contents with `algorithm`; group hashes carry its prefix
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Reuse hashes of unchanged files from earlier scans, and record new ones
+    pub fn with_hash_cache(mut self, cache: Arc<HashCache>) -> Self {
+        self.hash_cache = Some(cache);
+        self
+    }
+
+    pub fn scan(&self) -> DuplicateScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`
+    ///
+    /// A cancelled scan stops hashing; files not hashed by then drop out, so
+    /// every reported group is still confirmed by a full hash.
+    pub fn scan_with(&self, ctx: &ScanContext) -> DuplicateScanResult {
+        ctx.start_phase("walking", 0);
+        let candidates = collect_candidates(&self.roots, &self.exclusions, self.min_size, ctx);
+
+        let mut groups: Vec<DuplicateGroup> =
+            confirm_duplicates(candidates, self.algorithm, self.hash_cache.as_deref(), ctx)
+                .into_iter()
+                .map(|(hash, files)| self.build_group(hash, files))
+                .collect();
+        groups.sort_by_key(|g| Reverse(g.wasted_space));
+        ctx.finish();
+
+        DuplicateScanResult {
+            total_wasted_space: groups.iter().map(|g| g.wasted_space).sum(),
+            groups,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+
+    fn build_group(&self, hash: String, files: Vec<Candidate>) -> DuplicateGroup {
+        let size = files[0].size;
+        let mut files: Vec<DuplicateFile> = files
+            .into_iter()
+            .map(|c| DuplicateFile {
+                path: c.path,
+                modified: c.modified,
+                is_original: false,
+                is_protected: false,
+                is_selected: false,
+            })
+            .collect();
+
+        let original = pick_original(&files, &self.selection);
+        for (index, file) in files.iter_mut().enumerate() {
+            file.is_original = index == original;
+            // Pre-select every copy except the one we keep
+            file.is_selected = index != original;
+        }
+
+        DuplicateGroup {
+            hash,
+            size,
+            wasted_space: size * (files.len() as u64 - 1),
+            files,
+        }
+    }
+}
+
+/// Regular files of at least `min_size` bytes below `roots`, listing each
+/// path and each hardlinked file once
+pub(crate) fn collect_candidates(
+    roots: &[PathBuf],
+    exclusions: &ExclusionMatcher,
+    min_size: u64,
+    ctx: &ScanContext,
+) -> Vec<Candidate> {
+    let mut seen_paths = HashSet::new();
+    #[cfg(unix)]
+    let mut seen_inodes = HashSet::new();
+    let mut candidates = Vec::new();
+
+    'roots: for root in roots {
+        let walker = WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| !exclusions.is_excluded(e.path()));
+
+        for entry in walker {
+            if ctx.is_cancelled() {
+                break 'roots;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if let (Some(path), Some(error)) = (e.path(), e.io_error()) {
+                        ctx.skip_if_denied(path, error);
+                    }
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            ctx.visit(entry.path(), metadata.len());
+            if metadata.len() < min_size || !seen_paths.insert(entry.path().to_path_buf()) {
+                continue;
+            }
+
+            // Hardlinks share storage, removing one reclaims nothing
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                if !seen_inodes.insert((metadata.dev(), metadata.ino())) {
+                    continue;
+                }
+            }
+
+            candidates.push(Candidate {
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or_default(),
+                path: entry.into_path(),
+            });
+        }
+    }
+
+    candidates
+}
+
+/// Group `candidates` into sets of identical files keyed by their prefixed hash
+///
+/// Candidates are narrowed by size, then partial hash, then full hash, with
+/// `cache` consulted before any file is opened. Files that could not be read
+/// or were not hashed before cancellation drop out.
+pub(crate) fn confirm_duplicates(
+    candidates: Vec<Candidate>,
+    algorithm: HashAlgorithm,
+    cache: Option<&HashCache>,
+    ctx: &ScanContext,
+) -> Vec<(String, Vec<Candidate>)> {
+    // Stage 1: only files sharing a size can be duplicates
+    let by_size = group_by(candidates, |c| c.size);
+
+    // Stage 2: cheap partial hash of the first and last 4KB
+    ctx.start_phase("partial hashing", count_files(&by_size));
+    let by_partial = hash_groups(by_size, ctx, |c| match cache {
+        Some(cache) => cache.partial_hash(&c.path, algorithm),
+        None => hash_file_partial(&c.path, algorithm),
+    });
+
+    // Stage 3: full hash, unless the partial hash already read the whole file
+    let (covered, needs_full): (Vec<_>, Vec<_>) = by_partial
+        .into_iter()
+        .partition(|(_, files)| files[0].size <= PARTIAL_HASH_COVERS_WHOLE_FILE);
+    ctx.start_phase("full hashing", count_files(&needs_full));
+    let mut confirmed = hash_groups(needs_full, ctx, |c| match cache {
+        Some(cache) => cache.full_hash(&c.path, algorithm),
+        None => hash_file(&c.path, algorithm),
+    });
+    confirmed.extend(covered);
+    confirmed
+}
+
+/// Index of the file that should be kept as the original
+///
+/// Ties are broken by age, then path length, then path order so the choice
+/// is stable between scans.
+pub fn pick_original(files: &[DuplicateFile], selection: &OriginalSelection) -> usize {
+    pick_original_among(
+        files.iter().map(|f| (f.path.as_path(), f.modified)),
+        selection,
+    )
+}
+
+/// [`pick_original`] over `(path, modified)` pairs, for copies that are not files
+pub(crate) fn pick_original_among<'a>(
+    copies: impl Iterator<Item = (&'a Path, i64)>,
+    selection: &OriginalSelection,
+) -> usize {
+    let path_len = |path: &Path| path.as_os_str().len();
+    let by_age = |(path, modified): (&'a Path, i64)| (modified, path_len(path), path);
+
+    let best = match selection {
+        OriginalSelection::Oldest => copies.enumerate().min_by_key(|&(_, c)| by_age(c)),
+        OriginalSelection::ShortestPath => copies
+            .enumerate()
+            .min_by_key(|&(_, (path, modified))| (path_len(path), modified, path)),
+        OriginalSelection::PreferredFolder(folder) => copies
+            .enumerate()
+            .min_by_key(|&(_, c)| (!c.0.starts_with(folder), by_age(c))),
+    };
+
+    best.map(|(index, _)| index).unwrap_or(0)
+}
+
+fn group_by<K, F>(candidates: Vec<Candidate>, key: F) -> Vec<(K, Vec<Candidate>)>
+where
+    K: Eq + Hash,
+    F: Fn(&Candidate) -> K,
+{
+    let mut groups: HashMap<K, Vec<Candidate>> = HashMap::new();
+    for candidate in candidates {
+        groups.entry(key(&candidate)).or_default().push(candidate);
+    }
+    groups.into_iter().filter(|(_, g)| g.len() > 1).collect()
+}
+
+fn count_files<K>(groups: &[(K, Vec<Candidate>)]) -> u64 {
+    groups.iter().map(|(_, files)| files.len() as u64).sum()
+}
+
+/// Split each group by a hash computed in parallel, dropping unique files,
+/// files that could not be read and files skipped after cancellation
+fn hash_groups<K, F>(
+    groups: Vec<(K, Vec<Candidate>)>,
+    ctx: &ScanContext,
+    hasher: F,
+) -> Vec<(String, Vec<Candidate>)>
+where
+    K: Send,
+    F: Fn(&Candidate) -> std::io::Result<String> + Sync,
+{
+    groups
+        .into_par_iter()
+        .flat_map(|(_, files)| {
+            let hashed: Vec<(String, Candidate)> = files
+                .into_par_iter()
+                .filter(|_| !ctx.is_cancelled())
+                .inspect(|c| ctx.visit(&c.path, c.size))
+                .filter_map(|c| match hasher(&c) {
+                    Ok(hash) => Some((hash, c)),
+                    Err(e) => {
+                        log::debug!("Could not hash {}: {}", c.path.display(), e);
+                        ctx.skip_if_denied(&c.path, &e);
+                        None
+                    }
+                })
+                .collect();
+
+            let mut split: HashMap<String, Vec<Candidate>> = HashMap::new();
+            for (hash, candidate) in hashed {
+                split.entry(hash).or_default().push(candidate);
+            }
+            split
+                .into_iter()
+                .filter(|(_, g)| g.len() > 1)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_file;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn dup(path: &str, modified: i64) -> DuplicateFile {
+        DuplicateFile {
+            path: PathBuf::from(path),
+            modified,
+            is_original: false,
+            is_protected: false,
+            is_selected: false,
+        }
+    }
+
+    #[test]
+    fn test_pick_original_rules() {
+        let files = vec![
+            dup("/Users/me/Downloads/photo copy.jpg", 100),
+            dup("/Users/me/Pictures/2020/photo.jpg", 50),
+            dup("/Users/me/a.jpg", 200),
+        ];
+
+        assert_eq!(pick_original(&files, &OriginalSelection::Oldest), 1);
+        assert_eq!(pick_original(&files, &OriginalSelection::ShortestPath), 2);
+        assert_eq!(
+            pick_original(
+                &files,
+                &OriginalSelection::PreferredFolder(PathBuf::from("/Users/me/Downloads"))
+            ),
+            0
+        );
+        // No copy in the preferred folder falls back to the oldest
+        assert_eq!(
+            pick_original(
+                &files,
+                &OriginalSelection::PreferredFolder(PathBuf::from("/Volumes/Backup"))
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn test_scan_finds_small_and_large_duplicates() {
+        let root = tempdir().unwrap();
+        let small = b"same small content";
+        write_file(&root.path().join("a/small.txt"), small);
+        write_file(&root.path().join("b/small.txt"), small);
+        write_file(&root.path().join("c/small-copy.txt"), small);
+        write_file(&root.path().join("unique.txt"), b"different content!");
+
+        // Large files that share their first and last 4KB but differ in the middle
+        let mut large = vec![7u8; 20_000];
+        write_file(&root.path().join("large1.bin"), &large);
+        write_file(&root.path().join("large2.bin"), &large);
+        large[10_000] = 8;
+        write_file(&root.path().join("large3.bin"), &large);
+
+        write_file(&root.path().join("empty1"), b"");
+        write_file(&root.path().join("empty2"), b"");
+
+        let result = DuplicateScanner::new(vec![root.path().to_path_buf()]).scan();
+
+        assert_eq!(result.groups.len(), 2);
+        assert_eq!(result.groups[0].size, 20_000);
+        assert_eq!(result.groups[0].files.len(), 2);
+        assert!(result.groups[0].hash.starts_with("sha256:"));
+        assert_eq!(result.groups[1].files.len(), 3);
+        assert_eq!(result.groups[1].wasted_space, 2 * small.len() as u64);
+        assert_eq!(result.total_wasted_space, 20_000 + 2 * small.len() as u64);
+
+        for group in &result.groups {
+            assert_eq!(group.files.iter().filter(|f| f.is_original).count(), 1);
+            assert!(group.files.iter().all(|f| f.is_selected != f.is_original));
+        }
+    }
+
+    #[test]
+    fn test_hash_algorithm_is_prefixed() {
+        let root = tempdir().unwrap();
+        write_file(&root.path().join("a.bin"), &[9u8; 10_000]);
+        write_file(&root.path().join("b.bin"), &[9u8; 10_000]);
+
+        for (algorithm, prefix) in [
+            (HashAlgorithm::Blake3, "blake3:"),
+            (HashAlgorithm::Xxh3, "xxh3:"),
+        ] {
+            let result = DuplicateScanner::new(vec![root.path().to_path_buf()])
+                .with_hash_algorithm(algorithm)
+                .scan();
+            assert_eq!(result.groups.len(), 1);
+            assert!(result.groups[0].hash.starts_with(prefix));
+        }
+    }
+
+    #[test]
+    fn test_scan_respects_exclusions_and_preferred_folder() {
+        let root = tempdir().unwrap();
+        write_file(&root.path().join("keep/x.txt"), b"duplicate");
+        write_file(&root.path().join("other/x.txt"), b"duplicate");
+        write_file(&root.path().join("excluded/x.txt"), b"duplicate");
+
+        let result = DuplicateScanner::new(vec![root.path().to_path_buf()])
+            .with_exclusions(ExclusionMatcher::new(&[root.path().join("excluded")]))
+            .with_selection(OriginalSelection::PreferredFolder(root.path().join("keep")))
+            .scan();
+
+        assert_eq!(result.groups.len(), 1);
+        let group = &result.groups[0];
+        assert_eq!(group.files.len(), 2);
+        let original = group.files.iter().find(|f| f.is_original).unwrap();
+        assert!(original.path.starts_with(root.path().join("keep")));
+    }
+
+    #[test]
+    fn test_cancelled_scan_is_incomplete() {
+        let root = tempdir().unwrap();
+        write_file(&root.path().join("a.txt"), b"duplicate");
+        write_file(&root.path().join("b.txt"), b"duplicate");
+
+        let ctx = ScanContext::new("cancelled");
+        ctx.cancel();
+        let result = DuplicateScanner::new(vec![root.path().to_path_buf()]).scan_with(&ctx);
+        assert!(result.incomplete);
+        assert!(result.groups.is_empty());
+    }
+
+    #[test]
+    fn test_hash_cache_is_filled_and_reused() {
+        let root = tempdir().unwrap();
+        let large = vec![5u8; 20_000];
+        write_file(&root.path().join("a.bin"), &large);
+        write_file(&root.path().join("b.bin"), &large);
+        write_file(&root.path().join("c.txt"), b"small");
+        write_file(&root.path().join("d.txt"), b"small");
+
+        let cache = Arc::new(HashCache::default());
+        let scanner =
+            DuplicateScanner::new(vec![root.path().to_path_buf()]).with_hash_cache(cache.clone());
+        let first = scanner.scan();
+        assert_eq!(cache.len(), 4);
+        let second = scanner.scan();
+        assert_eq!(cache.len(), 4);
+
+        let hashes = |result: &DuplicateScanResult| -> Vec<String> {
+            result.groups.iter().map(|g| g.hash.clone()).collect()
+        };
+        assert_eq!(first.groups.len(), 2);
+        assert_eq!(hashes(&first), hashes(&second));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinks_are_not_duplicates() {
+        let root = tempdir().unwrap();
+        write_file(&root.path().join("original.txt"), b"linked content");
+        fs::hard_link(
+            root.path().join("original.txt"),
+            root.path().join("link.txt"),
+        )
+        .unwrap();
+
+        let result = DuplicateScanner::new(vec![root.path().to_path_buf()]).scan();
+        assert!(result.groups.is_empty());
+    }
+}
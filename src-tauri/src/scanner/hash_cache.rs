@@ -0,0 +1,364 @@
+use crate::models::config::HashAlgorithm;
+use crate::scanner::dir_index::nanos_since_epoch;
+use crate::utils::fs::{file_id, write_atomic};
+use crate::utils::hash::{hash_file, hash_file_partial};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub const HASH_CACHE_FILE_NAME: &str = "hash_cache.json";
+
+/// Version of the on-disk format; a cache written by any other version is discarded
+pub const HASH_CACHE_SCHEMA_VERSION: u32 = 2;
+
+/// Entries kept when the cache is saved, about 50 MB on disk; the least
+/// recently used are dropped first
+pub const MAX_HASH_CACHE_ENTRIES: usize = 200_000;
+
+/// Files modified this close to when they were hashed are read again, since
+/// a write within the same timestamp tick would not move their mtime
+const RACY_WINDOW_NS: u64 = 2_000_000_000;
+
+/// Digests of one file, valid while its size and mtime are unchanged
+///
+/// Digests carry their algorithm's prefix; one from another algorithm is a miss.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHash {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    modified: u64,
+    hashed_at: u64,
+    partial: Option<String>,
+    full: Option<String>,
+    /// Lookup counter value when the entry was last used, for pruning
+    last_used: u64,
+}
+
+impl CachedHash {
+    fn is_current(&self, size: u64, modified: u64) -> bool {
+        size == self.size
+            && modified == self.modified
+            && self.modified.saturating_add(RACY_WINDOW_NS) <= self.hashed_at
+    }
+}
+
+#[derive(Clone, Copy)]
+enum HashKind {
+    Partial,
+    Full,
+}
+
+impl HashKind {
+    fn calculate(self, path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+        match self {
+            Self::Partial => hash_file_partial(path, algorithm),
+            Self::Full => hash_file(path, algorithm),
+        }
+    }
+
+    fn slot(self, entry: &mut CachedHash) -> &mut Option<String> {
+        match self {
+            Self::Partial => &mut entry.partial,
+            Self::Full => &mut entry.full,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<CachedHash>,
+}
+
+/// Partial and full file hashes from earlier scans, keyed by device and
+/// inode, so a rescan only reads files whose size or mtime changed
+#[derive(Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<(u64, u64), CachedHash>>,
+    ticks: AtomicU64,
+}
+
+impl HashCache {
+    /// Number of files with a cached hash
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// [`hash_file_partial`], reusing the cached digest while the file is unchanged
+    pub fn partial_hash(&self, path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+        self.hash(path, HashKind::Partial, algorithm)
+    }
+
+    /// [`hash_file`], reusing the cached digest while the file is unchanged
+    pub fn full_hash(&self, path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+        self.hash(path, HashKind::Full, algorithm)
+    }
+
+    /// Drop the least recently used entries until at most `max_entries` remain
+    pub fn prune(&self, max_entries: usize) {
+        let mut entries = self.lock();
+        if entries.len() <= max_entries {
+            return;
+        }
+        let mut last_used: Vec<u64> = entries.values().map(|e| e.last_used).collect();
+        let excess = entries.len() - max_entries;
+        let (_, &mut cutoff, _) = last_used.select_nth_unstable(excess - 1);
+        entries.retain(|_, e| e.last_used > cutoff);
+    }
+
+    fn hash(&self, path: &Path, kind: HashKind, algorithm: HashAlgorithm) -> io::Result<String> {
+        // Only the metadata is read until the cache misses
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok().and_then(nanos_since_epoch);
+        let (Some(id), Some(modified)) = (file_id(&metadata), modified) else {
+            return kind.calculate(path, algorithm);
+        };
+        let size = metadata.len();
+        let tick = self.ticks.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(entry) = self
+            .lock()
+            .get_mut(&id)
+            .filter(|e| e.is_current(size, modified))
+        {
+            entry.last_used = tick;
+            let hash = kind.slot(entry).as_ref();
+            if let Some(hash) = hash.filter(|h| h.split(':').next() == Some(algorithm.prefix())) {
+                return Ok(hash.clone());
+            }
+        }
+
+        // Taken before reading, so a write during the read leaves the entry racy
+        let hashed_at = nanos_since_epoch(SystemTime::now()).unwrap_or(0);
+        let hash = kind.calculate(path, algorithm)?;
+
+        let mut entries = self.lock();
+        let entry = entries.entry(id).or_insert_with(|| CachedHash {
+            dev: id.0,
+            ino: id.1,
+            size,
+            modified,
+            hashed_at,
+            partial: None,
+            full: None,
+            last_used: tick,
+        });
+        if !entry.is_current(size, modified) {
+            *entry = CachedHash {
+                size,
+                modified,
+                hashed_at,
+                partial: None,
+                full: None,
+                ..entry.clone()
+            };
+        }
+        entry.last_used = tick;
+        *kind.slot(entry) = Some(hash.clone());
+        Ok(hash)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(u64, u64), CachedHash>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// JSON file holding the hash cache
+pub struct HashCacheStore {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl HashCacheStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_entries: MAX_HASH_CACHE_ENTRIES,
+        }
+    }
+
+    /// Store kept in the given app data directory
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::new(dir.join(HASH_CACHE_FILE_NAME))
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Load the cache, starting empty if the file is missing, unreadable as
+    /// JSON or from another schema version
+    pub fn load(&self) -> io::Result<HashCache> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashCache::default()),
+            Err(e) => return Err(e),
+        };
+
+        match serde_json::from_slice::<CacheFile>(&bytes) {
+            Ok(file) if file.version == HASH_CACHE_SCHEMA_VERSION => {
+                let ticks = file.entries.iter().map(|e| e.last_used + 1).max();
+                Ok(HashCache {
+                    entries: Mutex::new(
+                        file.entries
+                            .into_iter()
+                            .map(|e| ((e.dev, e.ino), e))
+                            .collect(),
+                    ),
+                    ticks: AtomicU64::new(ticks.unwrap_or(0)),
+                })
+            }
+            Ok(file) => {
+                log::debug!("Discarding hash cache version {}", file.version);
+                Ok(HashCache::default())
+            }
+            Err(e) => {
+                log::debug!("Discarding unreadable hash cache: {}", e);
+                Ok(HashCache::default())
+            }
+        }
+    }
+
+    /// Prune the cache to the size limit and write it out
+    pub fn save(&self, cache: &HashCache) -> io::Result<()> {
+        cache.prune(self.max_entries);
+        let file = CacheFile {
+            version: HASH_CACHE_SCHEMA_VERSION,
+            entries: cache.lock().values().cloned().collect(),
+        };
+        let json = serde_json::to_vec(&file)?;
+        write_atomic(&self.path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash::calculate_hash;
+    use std::fs::File;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    /// Write a file and backdate it so its entry falls outside the racy window
+    fn write_backdated(path: &Path, data: &[u8]) -> SystemTime {
+        fs::write(path, data).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        modified
+    }
+
+    #[test]
+    fn test_unchanged_files_are_not_read_again() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("movie.mov");
+        let modified = write_backdated(&path, b"original");
+        let cache = HashCache::default();
+        let hash = cache.full_hash(&path, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(hash, format!("sha256:{}", calculate_hash(&path).unwrap()));
+        assert_eq!(
+            cache.partial_hash(&path, HashAlgorithm::Sha256).unwrap(),
+            hash
+        );
+
+        // Same size and restored mtime: the stale digest is trusted
+        fs::write(&path, b"modified").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(cache.full_hash(&path, HashAlgorithm::Sha256).unwrap(), hash);
+
+        // A new mtime or size invalidates both digests
+        write_backdated(&path, b"modified again");
+        let rehashed = cache.full_hash(&path, HashAlgorithm::Sha256).unwrap();
+        assert_ne!(rehashed, hash);
+        assert_eq!(
+            rehashed,
+            format!("sha256:{}", calculate_hash(&path).unwrap())
+        );
+        assert_eq!(cache.len(), 1);
+
+        // Another algorithm is not served the cached digest
+        assert!(cache
+            .full_hash(&path, HashAlgorithm::Blake3)
+            .unwrap()
+            .starts_with("blake3:"));
+    }
+
+    #[test]
+    fn test_recently_modified_files_are_always_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fresh.txt");
+        fs::write(&path, b"first").unwrap();
+        let cache = HashCache::default();
+        let first = cache.full_hash(&path, HashAlgorithm::Sha256).unwrap();
+
+        fs::write(&path, b"secnd").unwrap();
+        assert_ne!(
+            cache.full_hash(&path, HashAlgorithm::Sha256).unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn test_store_prunes_least_recently_used() {
+        let data = tempdir().unwrap();
+        let files = tempdir().unwrap();
+        let store = HashCacheStore::in_dir(data.path()).with_max_entries(2);
+        assert!(store.load().unwrap().is_empty());
+
+        let cache = HashCache::default();
+        let paths: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = files.path().join(name);
+                write_backdated(&path, name.as_bytes());
+                cache.partial_hash(&path, HashAlgorithm::Sha256).unwrap();
+                path
+            })
+            .collect();
+        // Using "a" again leaves "b" as the least recently used
+        cache
+            .partial_hash(&paths[0], HashAlgorithm::Sha256)
+            .unwrap();
+        store.save(&cache).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        let ids: Vec<_> = paths
+            .iter()
+            .map(|p| file_id(&fs::metadata(p).unwrap()).unwrap())
+            .collect();
+        let entries = loaded.lock();
+        assert!(entries.contains_key(&ids[0]));
+        assert!(!entries.contains_key(&ids[1]));
+        assert!(entries.contains_key(&ids[2]));
+        drop(entries);
+        assert!(loaded.ticks.load(Ordering::Relaxed) > 3);
+
+        let stale = serde_json::json!({ "version": HASH_CACHE_SCHEMA_VERSION + 1, "entries": [] });
+        fs::write(data.path().join(HASH_CACHE_FILE_NAME), stale.to_string()).unwrap();
+        assert!(store.load().unwrap().is_empty());
+        fs::write(data.path().join(HASH_CACHE_FILE_NAME), "not json").unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+}
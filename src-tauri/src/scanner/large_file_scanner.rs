@@ -0,0 +1,223 @@
+use crate::analyzer::media_classifier::classify_file;
+use crate::models::config::AppConfig;
+use crate::models::scan_result::{LargeFile, LargeFileScanResult};
+use crate::scanner::now_timestamp;
+use crate::scanner::progress::ScanContext;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::volumes;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Finds files at or above a size threshold below a set of roots
+pub struct LargeFileScanner {
+    roots: Vec<PathBuf>,
+    threshold_bytes: u64,
+    exclusions: ExclusionMatcher,
+}
+
+impl LargeFileScanner {
+    pub fn new(roots: Vec<PathBuf>, threshold_bytes: u64) -> Self {
+        Self {
+            roots,
+            threshold_bytes,
+            exclusions: ExclusionMatcher::default(),
+        }
+    }
+
+    /// Build a scanner from the user's settings
+    ///
+    /// Scans `custom_scan_paths` when any are configured and the home
+    /// directory otherwise, plus external volumes if the settings ask for them.
+    pub fn from_config(config: &AppConfig, home: &Path) -> Self {
+        let volumes = if config.scan_locations.include_external_volumes {
+            volumes::list_volumes()
+        } else {
+            Vec::new()
+        };
+        let roots = volumes::scan_roots(&config.scan_locations, home, &volumes);
+        Self::new(
+            roots,
+            config.large_file_threshold_mb.saturating_mul(BYTES_PER_MB),
+        )
+        .with_exclusions(ExclusionMatcher::new(&config.exclusions))
+    }
+
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.exclusions.is_excluded(path)
+    }
+
+    pub fn scan(&self) -> LargeFileScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`, stopping the walk if it is cancelled
+    pub fn scan_with(&self, ctx: &ScanContext) -> LargeFileScanResult {
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<(PathBuf, std::fs::Metadata)> = Vec::new();
+
+        ctx.start_phase("walking", 0);
+        'roots: for root in &self.roots {
+            let walker = WalkDir::new(root)
+                .follow_links(false)
+                .into_iter()
+                .filter_entry(|e| !self.is_excluded(e.path()));
+
+            for entry in walker {
+                if ctx.is_cancelled() {
+                    break 'roots;
+                }
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        log::debug!("Skipping unreadable entry: {}", e);
+                        if let (Some(path), Some(error)) = (e.path(), e.io_error()) {
+                            ctx.skip_if_denied(path, error);
+                        }
+                        continue;
+                    }
+                };
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                ctx.visit(entry.path(), metadata.len());
+                // Overlapping roots must not report the same file twice
+                if metadata.len() >= self.threshold_bytes && seen.insert(entry.path().to_path_buf())
+                {
+                    candidates.push((entry.into_path(), metadata));
+                }
+            }
+        }
+
+        // Sniffing opens every candidate, so classify in parallel
+        ctx.start_phase("classifying", candidates.len() as u64);
+        let mut files: Vec<LargeFile> = candidates
+            .into_par_iter()
+            .map(|(path, metadata)| LargeFile {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or_default(),
+                accessed: metadata
+                    .accessed()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+                media_type: classify_file(&path),
+                thumbnail_path: None,
+                path,
+            })
+            .collect();
+        files.sort_by_key(|f| Reverse(f.size));
+        ctx.finish();
+
+        LargeFileScanResult {
+            total_size: files.iter().map(|f| f.size).sum(),
+            files,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{
+        AppearanceConfig, AutoCleanConfig, AutoCleanSchedule, ScanLocations, Theme, UserProfile,
+    };
+    use crate::models::scan_result::MediaType;
+    use crate::test_utils::write_file;
+    use tempfile::tempdir;
+
+    fn write_media(path: &Path, header: &[u8], bytes: usize) {
+        let mut data = header.to_vec();
+        data.resize(bytes, 0);
+        write_file(path, &data);
+    }
+
+    #[test]
+    fn test_scan_threshold_exclusions_and_classification() {
+        let root = tempdir().unwrap();
+        write_media(
+            &root.path().join("videos/clip.bin"),
+            b"\x00\x00\x00\x18ftypisom",
+            4096,
+        );
+        write_media(&root.path().join("docs/report.pdf"), b"%PDF-1.7", 2048);
+        write_media(&root.path().join("small.txt"), b"", 100);
+        write_media(&root.path().join("skip/huge.iso"), b"", 8192);
+
+        let scanner = LargeFileScanner::new(vec![root.path().to_path_buf()], 1024)
+            .with_exclusions(ExclusionMatcher::new(&[root.path().join("skip")]));
+        let result = scanner.scan();
+
+        assert_eq!(result.files.len(), 2);
+        assert_eq!(result.total_size, 4096 + 2048);
+        assert_eq!(result.files[0].name, "clip.bin");
+        assert_eq!(result.files[0].media_type, MediaType::Video);
+        assert!(result.files[0].modified > 0);
+        assert_eq!(result.files[1].media_type, MediaType::Document);
+    }
+
+    #[test]
+    fn test_overlapping_roots_do_not_duplicate() {
+        let root = tempdir().unwrap();
+        write_media(&root.path().join("a/big.zip"), b"PK\x03\x04", 2048);
+
+        let scanner =
+            LargeFileScanner::new(vec![root.path().to_path_buf(), root.path().join("a")], 1024);
+        assert_eq!(scanner.scan().files.len(), 1);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = AppConfig {
+            user_profile: UserProfile::Regular,
+            exclusions: vec![PathBuf::from("/ignore/me")],
+            large_file_threshold_mb: 100,
+            auto_clean: AutoCleanConfig {
+                enabled: false,
+                schedule: AutoCleanSchedule::OnDemand,
+                categories: vec![],
+                min_age_days: 30,
+            },
+            appearance: AppearanceConfig {
+                theme: Theme::System,
+                show_menu_bar_icon: false,
+            },
+            scan_locations: ScanLocations {
+                include_external_volumes: false,
+                custom_scan_paths: vec![],
+            },
+            hash_algorithm: Default::default(),
+        };
+
+        let scanner = LargeFileScanner::from_config(&config, Path::new("/Users/test"));
+        assert_eq!(scanner.roots, vec![PathBuf::from("/Users/test")]);
+        assert_eq!(scanner.threshold_bytes, 100 * BYTES_PER_MB);
+        assert!(scanner.is_excluded(Path::new("/ignore/me/file.mov")));
+        assert!(!scanner.is_excluded(Path::new("/ignore/menu")));
+    }
+}
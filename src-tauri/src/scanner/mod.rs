@@ -0,0 +1,103 @@
+pub mod cache_scanner;
+pub mod dir_index;
+pub mod duplicate_folder_scanner;
+pub mod duplicate_scanner;
+pub mod hash_cache;
+pub mod large_file_scanner;
+pub mod orphan_scanner;
+pub mod progress;
+pub mod similar_image_scanner;
+pub mod usage_tree;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Current time as a Unix timestamp, used for `scanned_at` fields
+pub fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Number of whole days elapsed since `time`, or `None` if it lies in the future
+pub fn age_in_days(time: SystemTime) -> Option<u32> {
+    SystemTime::now()
+        .duration_since(time)
+        .ok()
+        .map(|elapsed| (elapsed.as_secs() / SECONDS_PER_DAY) as u32)
+}
+
+/// Derive a display name from a reverse-DNS bundle identifier
+///
+/// `com.spotify.client` becomes "Spotify" and `com.apple.Safari` becomes "Safari".
+/// Generic trailing components such as "client" or "helper" are skipped.
+pub fn app_name_from_bundle_id(bundle_id: &str) -> Option<String> {
+    const GENERIC_COMPONENTS: &[&str] = &["app", "client", "desktop", "helper", "mac", "macos"];
+
+    let parts: Vec<&str> = bundle_id.split('.').filter(|p| !p.is_empty()).collect();
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let name = parts
+        .iter()
+        .skip(1)
+        .rev()
+        .find(|p| !GENERIC_COMPONENTS.contains(&p.to_lowercase().as_str()))?;
+
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+/// Whether a directory entry name looks like a bundle identifier (`com.vendor.App`)
+pub fn looks_like_bundle_id(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('.').collect();
+    parts.len() >= 3
+        && parts.iter().all(|p| !p.is_empty())
+        && parts[0].chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_age_in_days() {
+        let three_days_ago = SystemTime::now() - Duration::from_secs(3 * SECONDS_PER_DAY + 60);
+        assert_eq!(age_in_days(three_days_ago), Some(3));
+        assert_eq!(
+            age_in_days(SystemTime::now() + Duration::from_secs(3600)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_app_name_from_bundle_id() {
+        assert_eq!(
+            app_name_from_bundle_id("com.apple.Safari"),
+            Some("Safari".to_string())
+        );
+        assert_eq!(
+            app_name_from_bundle_id("com.spotify.client"),
+            Some("Spotify".to_string())
+        );
+        assert_eq!(
+            app_name_from_bundle_id("us.zoom.xos"),
+            Some("Xos".to_string())
+        );
+        assert_eq!(app_name_from_bundle_id("Homebrew"), None);
+    }
+
+    #[test]
+    fn test_looks_like_bundle_id() {
+        assert!(looks_like_bundle_id("com.apple.Safari"));
+        assert!(looks_like_bundle_id("org.mozilla.firefox"));
+        assert!(!looks_like_bundle_id("Homebrew"));
+        assert!(!looks_like_bundle_id("some.file"));
+        assert!(!looks_like_bundle_id(".hidden.dir.x"));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::analyzer::app_registry::AppRegistry;
+use crate::models::scan_result::{OrphanFileType, OrphanScanResult, OrphanedApp, OrphanedFile};
+use crate::scanner::progress::ScanContext;
+use crate::scanner::{app_name_from_bundle_id, looks_like_bundle_id, now_timestamp};
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::fs::disk_usage_with;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Library folders that hold per-app data, relative to the home directory
+pub(crate) const ORPHAN_SCAN_LOCATIONS: &[(&str, OrphanFileType)] = &[
+    ("Library/Preferences", OrphanFileType::Preferences),
+    (
+        "Library/Application Support",
+        OrphanFileType::ApplicationSupport,
+    ),
+    ("Library/Caches", OrphanFileType::Cache),
+    (
+        "Library/Saved Application State",
+        OrphanFileType::SavedState,
+    ),
+    ("Library/Containers", OrphanFileType::Container),
+];
+
+/// Finds Library data left behind by applications that are no longer installed
+///
+/// Only entries named after a bundle identifier are considered, so folders
+/// such as `Application Support/Google` are never flagged by name alone.
+pub struct OrphanScanner {
+    home: PathBuf,
+    app_roots: Vec<PathBuf>,
+    exclusions: ExclusionMatcher,
+}
+
+impl OrphanScanner {
+    pub fn new(home: impl Into<PathBuf>, app_roots: Vec<PathBuf>) -> Self {
+        Self {
+            home: home.into(),
+            app_roots,
+            exclusions: ExclusionMatcher::default(),
+        }
+    }
+
+    pub fn for_current_user() -> Option<Self> {
+        let home = dirs::home_dir()?;
+        let app_roots = AppRegistry::default_roots(&home);
+        Some(Self::new(home, app_roots))
+    }
+
+    /// Leave out entries the user excluded
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    pub fn scan(&self) -> OrphanScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`, stopping early if it is cancelled
+    pub fn scan_with(&self, ctx: &ScanContext) -> OrphanScanResult {
+        ctx.start_phase("applications", 0);
+        let registry = AppRegistry::scan(&self.app_roots);
+        self.scan_with_registry(&registry, ctx)
+    }
+
+    pub fn scan_with_registry(
+        &self,
+        registry: &AppRegistry,
+        ctx: &ScanContext,
+    ) -> OrphanScanResult {
+        let mut by_bundle_id: BTreeMap<String, Vec<OrphanedFile>> = BTreeMap::new();
+
+        ctx.start_phase("leftovers", ORPHAN_SCAN_LOCATIONS.len() as u64);
+        'locations: for (relative, file_type) in ORPHAN_SCAN_LOCATIONS {
+            let dir = self.home.join(relative);
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::debug!("Skipping {}: {}", dir.display(), e);
+                    ctx.skip_if_denied(&dir, &e);
+                    continue;
+                }
+            };
+
+            for entry in entries.filter_map(|e| e.ok()) {
+                if ctx.is_cancelled() {
+                    break 'locations;
+                }
+                let path = entry.path();
+                if self.exclusions.is_excluded(&path) {
+                    continue;
+                }
+                let Some(bundle_id) = bundle_id_for_entry(&path, file_type) else {
+                    continue;
+                };
+                if is_system_bundle_id(&bundle_id) || registry.owns(&bundle_id) {
+                    continue;
+                }
+
+                let usage = disk_usage_with(&path, ctx);
+                for unreadable in &usage.unreadable {
+                    ctx.skip(unreadable);
+                }
+                let size = usage.logical;
+                by_bundle_id
+                    .entry(bundle_id)
+                    .or_default()
+                    .push(OrphanedFile {
+                        size,
+                        path,
+                        file_type: file_type.clone(),
+                    });
+            }
+        }
+
+        let mut orphaned_apps: Vec<OrphanedApp> = by_bundle_id
+            .into_iter()
+            .map(|(bundle_id, files)| OrphanedApp {
+                presumed_name: app_name_from_bundle_id(&bundle_id)
+                    .unwrap_or_else(|| bundle_id.clone()),
+                total_size: files.iter().map(|f| f.size).sum(),
+                bundle_id: Some(bundle_id),
+                files,
+            })
+            .collect();
+        orphaned_apps.sort_by_key(|app| Reverse(app.total_size));
+        ctx.finish();
+
+        OrphanScanResult {
+            total_size: orphaned_apps.iter().map(|a| a.total_size).sum(),
+            orphaned_apps,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+}
+
+/// Extract the bundle identifier an entry is named after
+///
+/// Preferences are `<bundle id>.plist` files and saved state folders are
+/// `<bundle id>.savedState`; everything else uses the bare identifier.
+fn bundle_id_for_entry(path: &Path, file_type: &OrphanFileType) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let id = match file_type {
+        OrphanFileType::Preferences => name.strip_suffix(".plist")?,
+        OrphanFileType::SavedState => name.strip_suffix(".savedState")?,
+        _ => name,
+    };
+
+    looks_like_bundle_id(id).then(|| id.to_string())
+}
+
+/// Apple components don't ship as `.app` bundles we can see, so never flag them
+fn is_system_bundle_id(bundle_id: &str) -> bool {
+    bundle_id.to_lowercase().starts_with("com.apple.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::app_registry::tests::create_app;
+    use crate::test_utils::write_filler;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_bundle_id_for_entry() {
+        assert_eq!(
+            bundle_id_for_entry(
+                Path::new("/p/com.vendor.App.plist"),
+                &OrphanFileType::Preferences
+            ),
+            Some("com.vendor.App".to_string())
+        );
+        assert_eq!(
+            bundle_id_for_entry(
+                Path::new("/s/com.vendor.App.savedState"),
+                &OrphanFileType::SavedState
+            ),
+            Some("com.vendor.App".to_string())
+        );
+        assert_eq!(
+            bundle_id_for_entry(Path::new("/a/Google"), &OrphanFileType::ApplicationSupport),
+            None
+        );
+        assert_eq!(
+            bundle_id_for_entry(
+                Path::new("/p/.GlobalPreferences.plist"),
+                &OrphanFileType::Preferences
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_scan_finds_leftovers() {
+        let home = tempdir().unwrap();
+        let apps = tempdir().unwrap();
+        create_app(apps.path(), "Keep.app", "com.keep.App", "Keep");
+
+        let lib = home.path().join("Library");
+        // Leftovers of an uninstalled app across every location
+        write_filler(&lib.join("Preferences/com.gone.Editor.plist"), 10);
+        write_filler(&lib.join("Application Support/com.gone.Editor/db"), 100);
+        write_filler(&lib.join("Caches/com.gone.Editor/cache"), 50);
+        write_filler(
+            &lib.join("Saved Application State/com.gone.Editor.savedState/window"),
+            5,
+        );
+        write_filler(&lib.join("Containers/com.gone.Editor/Data/file"), 20);
+        // Data owned by the installed app, its helper and Apple
+        write_filler(&lib.join("Preferences/com.keep.App.plist"), 10);
+        write_filler(&lib.join("Caches/com.keep.App.helper/cache"), 10);
+        write_filler(&lib.join("Preferences/com.apple.finder.plist"), 10);
+        // Non bundle-id folders are left alone
+        write_filler(&lib.join("Application Support/Google/data"), 10);
+        // Another orphan
+        write_filler(&lib.join("Caches/org.old.Tool/cache"), 1);
+
+        let scanner = OrphanScanner::new(home.path(), vec![apps.path().to_path_buf()]);
+        let result = scanner.scan();
+
+        assert_eq!(result.orphaned_apps.len(), 2);
+        assert_eq!(result.total_size, 186);
+
+        let editor = &result.orphaned_apps[0];
+        assert_eq!(editor.bundle_id.as_deref(), Some("com.gone.Editor"));
+        assert_eq!(editor.presumed_name, "Editor");
+        assert_eq!(editor.total_size, 185);
+        assert_eq!(editor.files.len(), 5);
+        for file_type in [
+            OrphanFileType::Preferences,
+            OrphanFileType::ApplicationSupport,
+            OrphanFileType::Cache,
+            OrphanFileType::SavedState,
+            OrphanFileType::Container,
+        ] {
+            assert!(editor.files.iter().any(|f| f.file_type == file_type));
+        }
+
+        assert_eq!(
+            result.orphaned_apps[1].bundle_id.as_deref(),
+            Some("org.old.Tool")
+        );
+
+        let result = scanner
+            .with_exclusions(ExclusionMatcher::new(&[PathBuf::from("**/org.old.Tool")]))
+            .scan();
+        assert_eq!(result.orphaned_apps.len(), 1);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::models::scan_result::ScanProgress;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Event name the frontend listens on
+pub const SCAN_PROGRESS_EVENT: &str = "scan-progress";
+
+/// Minimum time between progress events, matching the frontend's `SCAN_DEBOUNCE_MS`
+pub const SCAN_DEBOUNCE_MS: u64 = 100;
+
+type Reporter = Box<dyn Fn(ScanProgress) + Send + Sync>;
+
+struct PhaseState {
+    phase: String,
+    total: u64,
+    last_report: Option<Instant>,
+}
+
+/// Progress counters and cancellation flag shared by a running scan
+///
+/// Scanners call [`visit`](Self::visit) for every entry and check
+/// [`is_cancelled`](Self::is_cancelled) between entries; progress is forwarded
+/// to the reporter at most once per debounce interval.
+pub struct ScanContext {
+    scan_id: String,
+    cancelled: AtomicBool,
+    files: AtomicU64,
+    bytes: AtomicU64,
+    state: Mutex<PhaseState>,
+    skipped: Mutex<Vec<PathBuf>>,
+    interval: Duration,
+    reporter: Option<Reporter>,
+}
+
+impl Default for ScanContext {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl ScanContext {
+    pub fn new(scan_id: impl Into<String>) -> Self {
+        Self {
+            scan_id: scan_id.into(),
+            cancelled: AtomicBool::new(false),
+            files: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            state: Mutex::new(PhaseState {
+                phase: String::new(),
+                total: 0,
+                last_report: None,
+            }),
+            skipped: Mutex::new(Vec::new()),
+            interval: Duration::from_millis(SCAN_DEBOUNCE_MS),
+            reporter: None,
+        }
+    }
+
+    pub fn with_reporter(
+        mut self,
+        reporter: impl Fn(ScanProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    pub fn scan_id(&self) -> &str {
+        &self.scan_id
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Start a new phase; `total` is the number of steps if known, 0 otherwise
+    ///
+    /// The visit count restarts for each phase while bytes keep adding up.
+    pub fn start_phase(&self, phase: &str, total: u64) {
+        let mut state = self.lock_state();
+        self.files.store(0, Ordering::Relaxed);
+        state.phase = phase.to_string();
+        state.total = total;
+        state.last_report = Some(Instant::now());
+        self.report(&state, None);
+    }
+
+    /// Count one visited entry of `bytes` bytes
+    pub fn visit(&self, path: &Path, bytes: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        if self.reporter.is_none() {
+            return;
+        }
+        let mut state = self.lock_state();
+        let due = state
+            .last_report
+            .map_or(true, |last| last.elapsed() >= self.interval);
+        if due {
+            state.last_report = Some(Instant::now());
+            self.report(&state, Some(path));
+        }
+    }
+
+    /// Note a path left out of the scan because it could not be read
+    pub fn skip(&self, path: &Path) {
+        self.skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(path.to_path_buf());
+    }
+
+    /// Note `path` as skipped if `error` means access to it was denied
+    pub fn skip_if_denied(&self, path: &Path, error: &io::Error) {
+        if error.kind() == io::ErrorKind::PermissionDenied {
+            self.skip(path);
+        }
+    }
+
+    /// Paths noted with [`skip`](Self::skip), sorted and without repeats
+    pub fn skipped(&self) -> Vec<PathBuf> {
+        let mut skipped = self
+            .skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        skipped.sort();
+        skipped.dedup();
+        skipped
+    }
+
+    /// Report the final counters regardless of the debounce interval
+    pub fn finish(&self) {
+        let state = self.lock_state();
+        self.report(&state, None);
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, PhaseState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn report(&self, state: &PhaseState, path: Option<&Path>) {
+        if let Some(reporter) = &self.reporter {
+            reporter(ScanProgress {
+                scan_id: self.scan_id.clone(),
+                phase: state.phase.clone(),
+                current: self.files.load(Ordering::Relaxed),
+                total: state.total,
+                current_path: path.map(Path::to_path_buf),
+                bytes_scanned: self.bytes.load(Ordering::Relaxed),
+            });
+        }
+    }
+}
+
+/// Scans that are currently running, so they can be cancelled by id
+#[derive(Default)]
+pub struct ScanRegistry {
+    scans: Mutex<HashMap<String, Arc<ScanContext>>>,
+}
+
+impl ScanRegistry {
+    pub fn register(&self, context: Arc<ScanContext>) {
+        self.lock().insert(context.scan_id().to_string(), context);
+    }
+
+    pub fn unregister(&self, scan_id: &str) {
+        self.lock().remove(scan_id);
+    }
+
+    /// Ask a running scan to stop; returns false if no such scan is running
+    pub fn cancel(&self, scan_id: &str) -> bool {
+        match self.lock().get(scan_id) {
+            Some(context) => {
+                context.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<ScanContext>>> {
+        self.scans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_is_throttled() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let context =
+            ScanContext::new("scan-1").with_reporter(move |p| sink.lock().unwrap().push(p));
+
+        context.start_phase("walking", 0);
+        for i in 0..1000 {
+            context.visit(Path::new("/tmp/file"), i);
+        }
+        context.finish();
+
+        let events = events.lock().unwrap();
+        // Start and finish always report; the visits all fall within one interval
+        assert_eq!(events.len(), 2);
+        let last = events.last().unwrap();
+        assert_eq!(last.scan_id, "scan-1");
+        assert_eq!(last.phase, "walking");
+        assert_eq!(last.current, 1000);
+        assert_eq!(last.bytes_scanned, (0..1000).sum::<u64>());
+    }
+
+    #[test]
+    fn test_registry_cancels_running_scans() {
+        let registry = ScanRegistry::default();
+        let context = Arc::new(ScanContext::new("scan-2"));
+        registry.register(context.clone());
+
+        assert!(!registry.cancel("other"));
+        assert!(registry.cancel("scan-2"));
+        assert!(context.is_cancelled());
+
+        registry.unregister("scan-2");
+        assert!(!registry.cancel("scan-2"));
+    }
+}
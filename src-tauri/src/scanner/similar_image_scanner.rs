@@ -0,0 +1,406 @@
+use crate::analyzer::media_classifier::classify_file;
+use crate::models::scan_result::{
+    MediaType, SimilarImage, SimilarImageGroup, SimilarImageScanResult,
+};
+use crate::scanner::duplicate_scanner::{collect_candidates, Candidate};
+use crate::scanner::now_timestamp;
+use crate::scanner::progress::ScanContext;
+use crate::utils::exclusions::ExclusionMatcher;
+use crate::utils::image_hash::{fingerprint, hamming_distance, ImageFingerprint};
+use image::ImageError;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+
+/// Hashes at most this many bits apart count as the same picture; about the
+/// spread of re-encoding and resizing a photo
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+struct Hashed {
+    candidate: Candidate,
+    fingerprint: ImageFingerprint,
+}
+
+/// Finds images that look alike, such as resized or re-encoded copies of a
+/// photo, by comparing perceptual hashes
+pub struct SimilarImageScanner {
+    roots: Vec<PathBuf>,
+    exclusions: ExclusionMatcher,
+    min_size: u64,
+    max_distance: u32,
+}
+
+impl SimilarImageScanner {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            exclusions: ExclusionMatcher::default(),
+            min_size: 1,
+            max_distance: DEFAULT_MAX_DISTANCE,
+        }
+    }
+
+    pub fn with_exclusions(mut self, exclusions: ExclusionMatcher) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    /// Ignore files smaller than `min_size` bytes
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size.max(1);
+        self
+    }
+
+    /// Group images whose hashes differ in at most `max_distance` of their 64 bits
+    pub fn with_max_distance(mut self, max_distance: u32) -> Self {
+        self.max_distance = max_distance.min(64);
+        self
+    }
+
+    pub fn scan(&self) -> SimilarImageScanResult {
+        self.scan_with(&ScanContext::default())
+    }
+
+    /// Scan while reporting progress to `ctx`
+    ///
+    /// A cancelled scan stops decoding; groups are formed from the images
+    /// hashed by then.
+    pub fn scan_with(&self, ctx: &ScanContext) -> SimilarImageScanResult {
+        ctx.start_phase("walking", 0);
+        let candidates = collect_candidates(&self.roots, &self.exclusions, self.min_size, ctx);
+
+        // Decoding is the slow part, so sniff and hash in parallel
+        ctx.start_phase("hashing images", candidates.len() as u64);
+        let hashed: Vec<Hashed> = candidates
+            .into_par_iter()
+            .filter(|_| !ctx.is_cancelled())
+            .inspect(|c| ctx.visit(&c.path, c.size))
+            .filter(|c| classify_file(&c.path) == MediaType::Image)
+            .filter_map(|candidate| match fingerprint(&candidate.path) {
+                Ok(Some(fingerprint)) => Some(Hashed {
+                    candidate,
+                    fingerprint,
+                }),
+                // Blank images would all share one hash
+                Ok(None) => None,
+                Err(e) => {
+                    log::debug!("Could not decode {}: {}", candidate.path.display(), e);
+                    if let ImageError::IoError(error) = &e {
+                        ctx.skip_if_denied(&candidate.path, error);
+                    }
+                    None
+                }
+            })
+            .collect();
+
+        let mut groups: Vec<SimilarImageGroup> = cluster(&hashed, self.max_distance)
+            .into_iter()
+            .map(|(original, members)| build_group(&hashed, original, members))
+            .collect();
+        groups.sort_by_key(|g| Reverse(g.wasted_space));
+        ctx.finish();
+
+        SimilarImageScanResult {
+            total_wasted_space: groups.iter().map(|g| g.wasted_space).sum(),
+            groups,
+            scanned_at: now_timestamp(),
+            incomplete: ctx.is_cancelled(),
+            skipped: ctx.skipped(),
+        }
+    }
+}
+
+/// Groups of similar images as (original, members)
+///
+/// The most preferred unclaimed image claims every unclaimed image within
+/// `max_distance` of it, so no member is further than that from its original,
+/// however a series of shots drifts from one to the next.
+fn cluster(hashed: &[Hashed], max_distance: u32) -> Vec<(usize, Vec<usize>)> {
+    let mut tree = BkTree::default();
+    for (index, image) in hashed.iter().enumerate() {
+        tree.insert(image.fingerprint.hash, index);
+    }
+
+    let mut order: Vec<usize> = (0..hashed.len()).collect();
+    order.sort_by_key(|&i| preference(&hashed[i]));
+    let mut claimed = vec![false; hashed.len()];
+    let mut groups = Vec::new();
+    for original in order {
+        if claimed[original] {
+            continue;
+        }
+        claimed[original] = true;
+        let mut members = vec![original];
+        tree.within(hashed[original].fingerprint.hash, max_distance, |other| {
+            if !claimed[other] {
+                claimed[other] = true;
+                members.push(other);
+            }
+        });
+        if members.len() > 1 {
+            groups.push((original, members));
+        }
+    }
+    groups
+}
+
+/// Sort key putting the best original first: most pixels, then the larger
+/// file as the less compressed copy, then the older one
+fn preference(image: &Hashed) -> (Reverse<u64>, Reverse<u64>, i64, &Path) {
+    let pixels = u64::from(image.fingerprint.width) * u64::from(image.fingerprint.height);
+    (
+        Reverse(pixels),
+        Reverse(image.candidate.size),
+        image.candidate.modified,
+        &image.candidate.path,
+    )
+}
+
+fn build_group(hashed: &[Hashed], original: usize, members: Vec<usize>) -> SimilarImageGroup {
+    let original_hash = hashed[original].fingerprint.hash;
+
+    let mut images: Vec<SimilarImage> = members
+        .iter()
+        .map(|&i| {
+            let Hashed {
+                candidate,
+                fingerprint,
+            } = &hashed[i];
+            SimilarImage {
+                path: candidate.path.clone(),
+                size: candidate.size,
+                width: fingerprint.width,
+                height: fingerprint.height,
+                modified: candidate.modified,
+                hash: format!("dhash:{:016x}", fingerprint.hash),
+                distance: hamming_distance(fingerprint.hash, original_hash),
+                is_original: i == original,
+                is_protected: false,
+                is_selected: false,
+            }
+        })
+        .collect();
+    images.sort_by(|a, b| {
+        b.is_original
+            .cmp(&a.is_original)
+            .then(a.distance.cmp(&b.distance))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    SimilarImageGroup {
+        wasted_space: images
+            .iter()
+            .filter(|i| !i.is_original)
+            .map(|i| i.size)
+            .sum(),
+        images,
+    }
+}
+
+/// Metric tree over hashes, so finding the neighbours of each image does not
+/// compare it against every other one
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    item: usize,
+    /// Child nodes by their distance from this node
+    children: Vec<(u32, usize)>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        let new = self.nodes.len();
+        if new > 0 {
+            let mut current = 0;
+            loop {
+                let distance = hamming_distance(self.nodes[current].hash, hash);
+                match self.nodes[current]
+                    .children
+                    .iter()
+                    .find(|(d, _)| *d == distance)
+                {
+                    Some(&(_, child)) => current = child,
+                    None => {
+                        self.nodes[current].children.push((distance, new));
+                        break;
+                    }
+                }
+            }
+        }
+        self.nodes.push(BkNode {
+            hash,
+            item,
+            children: Vec::new(),
+        });
+    }
+
+    /// Call `found` with every item whose hash is at most `radius` from `hash`
+    fn within(&self, hash: u64, radius: u32, mut found: impl FnMut(usize)) {
+        let mut pending = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(index) = pending.pop() {
+            let node = &self.nodes[index];
+            let distance = hamming_distance(node.hash, hash);
+            if distance <= radius {
+                found(node.item);
+            }
+            // By the triangle inequality, matches only sit below children in this band
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| d.abs_diff(distance) <= radius)
+                    .map(|&(_, child)| child),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::fs;
+
+    /// A smooth two-colour pattern that survives resizing and JPEG encoding
+    fn photo(width: u32, height: u32, invert: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let mut value = (x * 255 / width + (y * 128 / height)) % 256;
+            if invert {
+                value = 255 - value;
+            }
+            image::Rgb([value as u8, (y * 255 / height) as u8, 90])
+        }))
+    }
+
+    #[test]
+    fn test_resized_and_reencoded_copies_are_grouped() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path();
+        photo(800, 600, false)
+            .save_with_format(dir.join("original.png"), ImageFormat::Png)
+            .unwrap();
+        photo(800, 600, false)
+            .resize_exact(200, 150, image::imageops::FilterType::Triangle)
+            .save_with_format(dir.join("small.jpg"), ImageFormat::Jpeg)
+            .unwrap();
+        // Wrong extension: the contents decide
+        photo(800, 600, false)
+            .resize_exact(400, 300, image::imageops::FilterType::Triangle)
+            .save_with_format(dir.join("medium.dat"), ImageFormat::Jpeg)
+            .unwrap();
+        photo(800, 600, true)
+            .save_with_format(dir.join("other.png"), ImageFormat::Png)
+            .unwrap();
+        fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+        fs::write(dir.join("broken.jpg"), b"\xFF\xD8\xFF broken").unwrap();
+
+        let result = SimilarImageScanner::new(vec![dir.to_path_buf()]).scan();
+        assert_eq!(result.groups.len(), 1);
+        let group = &result.groups[0];
+        let names: Vec<_> = group
+            .images
+            .iter()
+            .map(|i| i.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "original.png");
+
+        let original = &group.images[0];
+        assert!(original.is_original);
+        assert_eq!((original.width, original.height), (800, 600));
+        assert_eq!(original.distance, 0);
+        assert!(original.hash.starts_with("dhash:"));
+        assert!(group.images[1..]
+            .iter()
+            .all(|i| !i.is_original && !i.is_selected && i.distance <= DEFAULT_MAX_DISTANCE));
+        assert_eq!(
+            group.wasted_space,
+            group.images[1..].iter().map(|i| i.size).sum::<u64>()
+        );
+        assert_eq!(result.total_wasted_space, group.wasted_space);
+
+        let strict = SimilarImageScanner::new(vec![dir.to_path_buf()])
+            .with_max_distance(0)
+            .scan();
+        assert!(strict.groups.iter().all(|g| g.images.len() < 3));
+    }
+
+    fn hashed(name: &str, side: u32, hash: u64) -> Hashed {
+        Hashed {
+            candidate: Candidate {
+                path: PathBuf::from(name),
+                size: 1000,
+                modified: 0,
+            },
+            fingerprint: ImageFingerprint {
+                width: side,
+                height: side,
+                hash,
+            },
+        }
+    }
+
+    #[test]
+    fn test_chains_do_not_stretch_groups_past_max_distance() {
+        // A-B and B-C are 6 bits apart, A-C 12
+        let (a, b, c) = (0, 0b11_1111, 0b1111_1111_1111);
+        let images = [
+            hashed("a", 400, a),
+            hashed("b", 200, b),
+            hashed("c", 100, c),
+        ];
+        let groups = cluster(&images, 10);
+        assert_eq!(groups, vec![(0, vec![0, 1])]);
+        let group = build_group(&images, 0, vec![0, 1]);
+        assert!(group.images.iter().all(|i| i.distance <= 10));
+
+        // With B as the best copy both neighbours are close enough to it
+        let images = [
+            hashed("a", 200, a),
+            hashed("b", 400, b),
+            hashed("c", 100, c),
+        ];
+        let (original, mut members) = cluster(&images, 10).pop().unwrap();
+        members.sort();
+        assert_eq!((original, members), (1, vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn test_blank_images_are_not_grouped() {
+        let root = tempfile::tempdir().unwrap();
+        for (name, shade) in [("black.png", 0u8), ("white.png", 255), ("grey.png", 128)] {
+            RgbImage::from_pixel(64, 64, image::Rgb([shade; 3]))
+                .save(root.path().join(name))
+                .unwrap();
+        }
+        let result = SimilarImageScanner::new(vec![root.path().to_path_buf()]).scan();
+        assert!(result.groups.is_empty());
+    }
+
+    #[test]
+    fn test_bk_tree_finds_everything_within_radius() {
+        let hashes = [0u64, 0b1, 0b11, 0b111_1111, u64::MAX, 0b1];
+        let mut tree = BkTree::default();
+        for (item, &hash) in hashes.iter().enumerate() {
+            tree.insert(hash, item);
+        }
+        for query in [0u64, 0b11, u64::MAX >> 1] {
+            for radius in [0, 1, 2, 8, 64] {
+                let mut found = Vec::new();
+                tree.within(query, radius, |item| found.push(item));
+                found.sort();
+                let expected: Vec<usize> = (0..hashes.len())
+                    .filter(|&i| hamming_distance(hashes[i], query) <= radius)
+                    .collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+}
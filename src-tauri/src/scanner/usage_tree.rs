@@ -0,0 +1,336 @@
+use crate::cleaner::safe_delete::new_entry_id;
+use crate::error::CleanMacError;
+use crate::models::scan_result::{UsageNode, UsageNodeKind, UsageTree};
+use crate::scanner::now_timestamp;
+use crate::scanner::progress::ScanContext;
+use crate::utils::fs::{allocated_size, DiskUsage, UsageWalker};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Breakdowns kept for drilling down; the oldest is dropped first
+pub const MAX_KEPT_TREES: usize = 4;
+
+/// A measured tree, kept so the UI can expand nodes without walking the disk again
+///
+/// Entries under `min_size` are not kept individually; each folder only
+/// records their combined size.
+pub struct SizedTree {
+    id: String,
+    entries: Vec<Entry>,
+    unreadable: Vec<PathBuf>,
+    incomplete: bool,
+    scanned_at: i64,
+}
+
+struct Entry {
+    path: PathBuf,
+    kind: UsageNodeKind,
+    usage: DiskUsage,
+    /// Indexes into `SizedTree::entries`, largest first
+    children: Vec<usize>,
+    /// Combined usage of the children under the minimum size
+    other: DiskUsage,
+}
+
+/// Entry measured during the walk, before it is flattened into a `SizedTree`
+struct Measured {
+    path: PathBuf,
+    kind: UsageNodeKind,
+    usage: DiskUsage,
+    children: Vec<Measured>,
+    other: DiskUsage,
+}
+
+impl SizedTree {
+    /// Measure everything below `root`, keeping entries of at least `min_size` bytes
+    ///
+    /// Sizes follow [`crate::utils::fs::disk_usage`]: hardlinks count once,
+    /// symlinks are not followed and other filesystems are skipped.
+    pub fn build(root: &Path, min_size: u64, ctx: &ScanContext) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(root)?;
+        ctx.start_phase("measuring", 0);
+        let builder = Builder {
+            walker: UsageWalker::new(&metadata),
+            min_size,
+            ctx,
+        };
+        let mut measured = builder.measure(root.to_path_buf(), &metadata);
+        ctx.finish();
+
+        let unreadable = std::mem::take(&mut measured.usage.unreadable);
+        let mut entries = Vec::new();
+        flatten(measured, &mut entries);
+        Ok(Self {
+            id: format!("usage-{}", new_entry_id()),
+            entries,
+            unreadable,
+            incomplete: ctx.is_cancelled(),
+            scanned_at: now_timestamp(),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The tree from the root down to `depth` levels
+    pub fn to_tree(&self, depth: usize) -> UsageTree {
+        UsageTree {
+            tree_id: self.id.clone(),
+            root: self.render(0, depth),
+            unreadable: self.unreadable.clone(),
+            incomplete: self.incomplete,
+            scanned_at: self.scanned_at,
+        }
+    }
+
+    /// The node with `node_id` and its children down to `depth` levels
+    pub fn node(&self, node_id: &str, depth: usize) -> Result<UsageNode, CleanMacError> {
+        node_id
+            .strip_prefix(&self.id)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .and_then(|index| index.parse::<usize>().ok())
+            .filter(|&index| index < self.entries.len())
+            .map(|index| self.render(index, depth))
+            .ok_or_else(|| {
+                CleanMacError::not_found(format!("No expandable node with id {}", node_id))
+            })
+    }
+
+    fn render(&self, index: usize, depth: usize) -> UsageNode {
+        let entry = &self.entries[index];
+        let has_other = entry.other.files > 0 || entry.other.logical > 0;
+
+        let mut children = Vec::new();
+        if depth > 0 {
+            children.extend(entry.children.iter().map(|&c| self.render(c, depth - 1)));
+            if has_other {
+                children.push(UsageNode {
+                    id: format!("{}:{}:other", self.id, index),
+                    name: "Other".to_string(),
+                    path: None,
+                    kind: UsageNodeKind::Other,
+                    size: entry.other.logical,
+                    allocated: entry.other.allocated,
+                    files: entry.other.files,
+                    has_children: false,
+                    children: Vec::new(),
+                });
+            }
+        }
+
+        UsageNode {
+            id: format!("{}:{}", self.id, index),
+            name: entry
+                .path
+                .file_name()
+                .unwrap_or(entry.path.as_os_str())
+                .to_string_lossy()
+                .to_string(),
+            path: Some(entry.path.clone()),
+            kind: entry.kind,
+            size: entry.usage.logical,
+            allocated: entry.usage.allocated,
+            files: entry.usage.files,
+            has_children: !entry.children.is_empty() || has_other,
+            children,
+        }
+    }
+}
+
+struct Builder<'a> {
+    walker: UsageWalker,
+    min_size: u64,
+    ctx: &'a ScanContext,
+}
+
+impl Builder<'_> {
+    fn measure(&self, path: PathBuf, metadata: &Metadata) -> Measured {
+        if !metadata.is_dir() {
+            let usage = self.walker.file(metadata);
+            self.ctx.visit(&path, usage.logical);
+            return Measured {
+                path,
+                kind: UsageNodeKind::File,
+                usage,
+                children: Vec::new(),
+                other: DiskUsage::default(),
+            };
+        }
+
+        let mut dir = Measured {
+            path,
+            kind: UsageNodeKind::Directory,
+            usage: DiskUsage::default(),
+            children: Vec::new(),
+            other: DiskUsage::default(),
+        };
+        if self.walker.crosses_device(metadata) || self.ctx.is_cancelled() {
+            return dir;
+        }
+        dir.usage.allocated = allocated_size(metadata);
+        let entries: Vec<_> = match fs::read_dir(&dir.path) {
+            Ok(entries) => entries.collect(),
+            Err(_) => {
+                dir.usage.unreadable.push(dir.path.clone());
+                return dir;
+            }
+        };
+
+        let children: Vec<Result<Measured, PathBuf>> = entries
+            .into_par_iter()
+            .map(|entry| {
+                let entry = entry.map_err(|_| dir.path.clone())?;
+                let path = entry.path();
+                // DirEntry::metadata does not traverse symlinks
+                match entry.metadata() {
+                    Ok(metadata) => Ok(self.measure(path, &metadata)),
+                    Err(_) => Err(path),
+                }
+            })
+            .collect();
+
+        for child in children {
+            let mut child = match child {
+                Ok(child) => child,
+                Err(path) => {
+                    dir.usage.unreadable.push(path);
+                    continue;
+                }
+            };
+            // Collect unreadable paths at the root instead of at every level
+            let unreadable = std::mem::take(&mut child.usage.unreadable);
+            dir.usage = std::mem::take(&mut dir.usage).merge(child.usage.clone());
+            dir.usage.unreadable.extend(unreadable);
+            if child.usage.logical >= self.min_size && child.usage.logical > 0 {
+                dir.children.push(child);
+            } else {
+                dir.other = std::mem::take(&mut dir.other).merge(child.usage);
+            }
+        }
+        dir
+    }
+}
+
+/// Move `measured` and its descendants into `entries`, returning its index
+fn flatten(mut measured: Measured, entries: &mut Vec<Entry>) -> usize {
+    let index = entries.len();
+    measured.children.sort_by_key(|c| Reverse(c.usage.logical));
+    entries.push(Entry {
+        path: measured.path,
+        kind: measured.kind,
+        usage: measured.usage,
+        children: Vec::new(),
+        other: measured.other,
+    });
+    let children = measured
+        .children
+        .into_iter()
+        .map(|child| flatten(child, entries))
+        .collect();
+    entries[index].children = children;
+    index
+}
+
+/// Recently measured trees, looked up by the node ids they hand out
+#[derive(Default)]
+pub struct UsageTreeStore {
+    trees: Mutex<VecDeque<Arc<SizedTree>>>,
+}
+
+impl UsageTreeStore {
+    pub fn insert(&self, tree: Arc<SizedTree>) {
+        let mut trees = self.lock();
+        trees.push_back(tree);
+        while trees.len() > MAX_KEPT_TREES {
+            trees.pop_front();
+        }
+    }
+
+    /// Load the node with `node_id` from the tree that issued it
+    pub fn expand(&self, node_id: &str, depth: usize) -> Result<UsageNode, CleanMacError> {
+        let tree = self
+            .lock()
+            .iter()
+            .find(|t| node_id.starts_with(&format!("{}:", t.id())))
+            .cloned()
+            .ok_or_else(|| {
+                CleanMacError::not_found("The disk usage tree has expired, please measure it again")
+            })?;
+        tree.node(node_id, depth)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Arc<SizedTree>>> {
+        self.trees.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn names(node: &UsageNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tree_is_sorted_and_aggregates_small_entries() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("big/nested")).unwrap();
+        fs::write(root.join("big/nested/blob"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("big/tiny"), b"x").unwrap();
+        fs::write(root.join("medium.bin"), vec![0u8; 2000]).unwrap();
+        fs::write(root.join("small.txt"), b"hello").unwrap();
+        fs::create_dir(root.join("empty")).unwrap();
+
+        let tree = SizedTree::build(root, 1000, &ScanContext::default()).unwrap();
+        let usage = tree.to_tree(1);
+        assert_eq!(usage.root.size, 5000 + 1 + 2000 + 5);
+        assert_eq!(usage.root.files, 4);
+        assert_eq!(names(&usage.root), vec!["big", "medium.bin", "Other"]);
+
+        let other = usage.root.children.last().unwrap();
+        assert_eq!(other.kind, UsageNodeKind::Other);
+        assert_eq!((other.size, other.files), (5, 1));
+
+        // Below the requested depth children are left for expand
+        let big = &usage.root.children[0];
+        assert!(big.has_children);
+        assert!(big.children.is_empty());
+        assert_eq!(big.kind, UsageNodeKind::Directory);
+        assert!(!usage.root.children[1].has_children);
+    }
+
+    #[test]
+    fn test_expand_by_node_id() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), vec![0u8; 100]).unwrap();
+
+        let store = UsageTreeStore::default();
+        let tree = Arc::new(SizedTree::build(dir.path(), 0, &ScanContext::default()).unwrap());
+        store.insert(tree.clone());
+        let a_id = tree.to_tree(1).root.children[0].id.clone();
+
+        let a = store.expand(&a_id, 2).unwrap();
+        assert_eq!(a.name, "a");
+        assert_eq!(names(&a), vec!["b"]);
+        assert_eq!(names(&a.children[0]), vec!["file"]);
+
+        assert!(store.expand(&format!("{}:999", tree.id()), 1).is_err());
+        assert!(store.expand("usage-unknown:0", 1).is_err());
+    }
+
+    #[test]
+    fn test_missing_root_is_an_error() {
+        let dir = tempdir().unwrap();
+        assert!(SizedTree::build(&dir.path().join("missing"), 0, &ScanContext::default()).is_err());
+    }
+}
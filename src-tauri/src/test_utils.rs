@@ -0,0 +1,15 @@
+//! Helpers shared by the unit tests
+
+use std::fs;
+use std::path::Path;
+
+/// Write `data` to `path`, creating its parent folders
+pub(crate) fn write_file(path: &Path, data: &[u8]) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, data).unwrap();
+}
+
+/// Write `bytes` bytes of filler to `path`, creating its parent folders
+pub(crate) fn write_filler(path: &Path, bytes: usize) {
+    write_file(path, &vec![b'x'; bytes]);
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+/// Bundle identifier from `tauri.conf.json`, which names the app's directories
+pub const APP_IDENTIFIER: &str = "com.cleanmac.app";
+
+/// Where the app keeps its data, resolved the way Tauri's `app_data_dir` does
+///
+/// Lets code running outside the Tauri app, such as the CLI, share the same files.
+pub fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Where the app keeps its settings, resolved the way Tauri's `app_config_dir` does
+pub fn app_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_matches_tauri_config() {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../../tauri.conf.json")).unwrap();
+        assert_eq!(config["identifier"], APP_IDENTIFIER);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::analyzer::developer_detector::DeveloperDetector;
+use crate::error::CleanMacError;
+use crate::models::config::AppConfig;
+use crate::utils::fs::write_atomic;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Version of the on-disk format written by this build
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// JSON file holding the user's settings
+pub struct ConfigStore {
+    path: PathBuf,
+}
+
+impl ConfigStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store kept in the given app config directory
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::new(dir.join(CONFIG_FILE_NAME))
+    }
+
+    /// Whether settings have been saved before
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Load the settings, falling back to defaults for a missing file or missing fields
+    pub fn load(&self) -> io::Result<AppConfig> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(AppConfig::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut value: Value = serde_json::from_slice(&bytes).map_err(invalid_data)?;
+        let Some(fields) = value.as_object_mut() else {
+            return Err(invalid_data("Config file is not a JSON object"));
+        };
+        // Files written before versioning have no `version`
+        let version = fields
+            .remove("version")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        if version > u64::from(CONFIG_SCHEMA_VERSION) {
+            return Err(invalid_data(format!(
+                "Config file version {} is newer than supported version {}",
+                version, CONFIG_SCHEMA_VERSION
+            )));
+        }
+
+        let defaults = serde_json::to_value(AppConfig::default())?;
+        fill_missing(&mut value, &defaults);
+        serde_json::from_value(value).map_err(invalid_data)
+    }
+
+    /// The saved settings or, before the first save, the defaults with the
+    /// profile the detected developer tools suggest
+    pub fn load_or_detect(&self) -> io::Result<AppConfig> {
+        if self.exists() {
+            return self.load();
+        }
+        let user_profile = DeveloperDetector::for_current_user()
+            .map(|detector| {
+                detector
+                    .with_cache_sizes(false)
+                    .detect()
+                    .suggested_profile()
+            })
+            .unwrap_or_default();
+        Ok(AppConfig {
+            user_profile,
+            ..Default::default()
+        })
+    }
+
+    /// The settings scans and cleaning apply: [`Self::load_or_detect`], validated
+    pub fn load_validated(&self) -> Result<AppConfig, CleanMacError> {
+        let config = self.load_or_detect()?;
+        config.validate().map_err(CleanMacError::ConfigInvalid)?;
+        Ok(config)
+    }
+
+    pub fn save(&self, config: &AppConfig) -> io::Result<()> {
+        let mut value = serde_json::to_value(config)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("version".to_string(), CONFIG_SCHEMA_VERSION.into());
+        }
+        let json = serde_json::to_vec_pretty(&value)?;
+        write_atomic(&self.path, &json)
+    }
+}
+
+/// Copy every field missing from `value` over from `defaults`, recursing into objects
+///
+/// Files saved by older versions lack fields added since, which then take
+/// the current defaults.
+fn fill_missing(value: &mut Value, defaults: &Value) {
+    let (Value::Object(fields), Value::Object(default_fields)) = (value, defaults) else {
+        return;
+    };
+    for (key, default) in default_fields {
+        match fields.get_mut(key) {
+            Some(existing) => fill_missing(existing, default),
+            None => {
+                fields.insert(key.clone(), default.clone());
+            }
+        }
+    }
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{Theme, UserProfile};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let dir = tempdir().unwrap();
+        let config = ConfigStore::in_dir(dir.path()).load().unwrap();
+        assert_eq!(config.large_file_threshold_mb, 100);
+        assert_eq!(config.user_profile, UserProfile::Regular);
+    }
+
+    #[test]
+    fn test_unversioned_file_is_migrated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            br#"{"large_file_threshold_mb": 250, "appearance": {"theme": "Dark"}}"#,
+        )
+        .unwrap();
+
+        let config = ConfigStore::new(&path).load().unwrap();
+        assert_eq!(config.large_file_threshold_mb, 250);
+        assert_eq!(config.appearance.theme, Theme::Dark);
+        assert!(config.appearance.show_menu_bar_icon);
+        assert!(config.exclusions.is_empty());
+    }
+
+    #[test]
+    fn test_save_round_trip_and_newer_version() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::in_dir(&dir.path().join("config"));
+        let config = AppConfig {
+            user_profile: UserProfile::Developer,
+            exclusions: vec![PathBuf::from("/Users/test/Projects")],
+            ..Default::default()
+        };
+        store.save(&config).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.user_profile, UserProfile::Developer);
+        assert_eq!(loaded.exclusions, config.exclusions);
+
+        let path = dir.path().join("config").join(CONFIG_FILE_NAME);
+        let raw: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(raw["version"], CONFIG_SCHEMA_VERSION);
+
+        fs::write(&path, br#"{"version": 99}"#).unwrap();
+        assert!(store.load().is_err());
+    }
+
+    #[test]
+    fn test_load_validated_rejects_invalid_settings() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::in_dir(dir.path());
+        store
+            .save(&AppConfig {
+                user_profile: UserProfile::Developer,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            store.load_validated().unwrap().user_profile,
+            UserProfile::Developer
+        );
+
+        store
+            .save(&AppConfig {
+                large_file_threshold_mb: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(matches!(
+            store.load_validated(),
+            Err(CleanMacError::ConfigInvalid(_))
+        ));
+    }
+}
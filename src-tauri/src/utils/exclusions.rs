@@ -0,0 +1,262 @@
+use crate::analyzer::cache_categorizer::{starts_with_folded, strip_prefix_folded};
+use crate::models::config::ExclusionExplanation;
+use crate::utils::fs::expand_tilde;
+use glob::{MatchOptions, Pattern};
+use std::path::{Path, PathBuf};
+
+/// Paths are matched ignoring case, as the default APFS volume does
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Compiled `AppConfig::exclusions`, with gitignore-style semantics
+///
+/// - `/abs/path` and `~/path` match that path exactly
+/// - patterns may contain globs, with `**` spanning folders
+/// - relative patterns such as `node_modules` or `**/node_modules` match at any depth
+/// - a leading `!` re-includes what an earlier pattern excluded
+///
+/// The last matching pattern wins. Excluding a folder excludes everything
+/// inside it, and as in gitignore, nothing inside an excluded folder can be
+/// re-included.
+#[derive(Debug, Clone, Default)]
+pub struct ExclusionMatcher {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    source: String,
+    negated: bool,
+    target: Target,
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    Exact(PathBuf),
+    Glob(Pattern),
+}
+
+impl Rule {
+    fn parse(source: &str) -> Result<Self, glob::PatternError> {
+        let (negated, body) = match source.strip_prefix('!') {
+            Some(body) => (true, body),
+            None => (false, source),
+        };
+        let body = match body.trim_end_matches('/') {
+            "" => body,
+            trimmed => trimmed,
+        };
+
+        let is_glob = body.contains(['*', '?', '[']);
+        let target = if body == "~" || body.starts_with("~/") || body.starts_with('/') {
+            if is_glob {
+                // Only the part after `~` is a pattern; the home path may contain `[` or `?`
+                let (base, rest) = match body.strip_prefix('~') {
+                    Some(rest) => (expand_tilde("~"), rest),
+                    None => (PathBuf::new(), body),
+                };
+                let base = Pattern::escape(&base.to_string_lossy());
+                Target::Glob(Pattern::new(&format!("{}{}", base, rest))?)
+            } else {
+                Target::Exact(expand_tilde(body))
+            }
+        } else {
+            let body = if is_glob {
+                body.to_string()
+            } else {
+                Pattern::escape(body)
+            };
+            match body.strip_prefix("**/") {
+                Some(_) => Target::Glob(Pattern::new(&body)?),
+                None => Target::Glob(Pattern::new(&format!("**/{}", body))?),
+            }
+        };
+
+        Ok(Self {
+            source: source.to_string(),
+            negated,
+            target,
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match &self.target {
+            Target::Exact(exact) => is_same_path(path, exact),
+            Target::Glob(pattern) => pattern.matches_path_with(path, MATCH_OPTIONS),
+        }
+    }
+}
+
+impl ExclusionMatcher {
+    /// Compile `patterns`; a pattern that is not a valid glob matches literally
+    pub fn new(patterns: &[PathBuf]) -> Self {
+        let rules = patterns
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .filter(|p| !p.trim().is_empty())
+            .map(|source| {
+                Rule::parse(&source).unwrap_or_else(|e| {
+                    log::warn!("Exclusion {} is not a valid pattern: {}", source, e);
+                    Rule {
+                        source: source.to_string(),
+                        negated: false,
+                        target: Target::Exact(expand_tilde(&source)),
+                    }
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Check that a pattern from the settings compiles
+    pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+        Rule::parse(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid exclusion pattern {}: {}", pattern, e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.explain(path).excluded
+    }
+
+    /// The first exact-path exclusion lying inside `path`, which removing
+    /// `path` would remove too
+    ///
+    /// Glob patterns are not considered, since checking them would mean walking `path`.
+    pub fn excluded_inside(&self, path: &Path) -> Option<&str> {
+        self.rules.iter().find_map(|rule| match &rule.target {
+            Target::Exact(exact)
+                if !rule.negated
+                    && !is_same_path(exact, path)
+                    && starts_with_folded(exact, path) =>
+            {
+                Some(rule.source.as_str())
+            }
+            _ => None,
+        })
+    }
+
+    /// Say whether `path` is excluded and which pattern decided it
+    pub fn explain(&self, path: &Path) -> ExclusionExplanation {
+        let mut explanation = ExclusionExplanation {
+            path: path.to_path_buf(),
+            excluded: false,
+            rule: None,
+            matched_path: None,
+        };
+        if self.rules.is_empty() {
+            return explanation;
+        }
+
+        // Outermost folder first, since an excluded folder excludes everything inside it
+        let mut ancestors: Vec<&Path> = path.ancestors().collect();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            let Some(rule) = self.rules.iter().rev().find(|r| r.matches(ancestor)) else {
+                continue;
+            };
+            explanation.rule = Some(rule.source.clone());
+            explanation.matched_path = Some(ancestor.to_path_buf());
+            if !rule.negated {
+                explanation.excluded = true;
+                break;
+            }
+        }
+        explanation
+    }
+}
+
+fn is_same_path(path: &Path, other: &Path) -> bool {
+    strip_prefix_folded(path, other).is_some_and(|rest| rest.as_os_str().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(patterns: &[&str]) -> ExclusionMatcher {
+        ExclusionMatcher::new(&patterns.iter().map(PathBuf::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_exact_paths_exclude_their_contents() {
+        let m = matcher(&["/ignore/me", "~/Projects/"]);
+        assert!(m.is_excluded(Path::new("/ignore/me")));
+        assert!(m.is_excluded(Path::new("/ignore/me/file.mov")));
+        assert!(!m.is_excluded(Path::new("/ignore/menu")));
+        assert!(!m.is_excluded(Path::new("/ignore")));
+        assert!(m.is_excluded(&expand_tilde("~/Projects/app/target")));
+        assert!(m.is_excluded(&expand_tilde("~/projects")));
+        assert!(m.is_excluded(Path::new("/IGNORE/Me/file.mov")));
+    }
+
+    #[test]
+    fn test_globs() {
+        let m = matcher(&["**/node_modules", "*.log", "/data/*/cache"]);
+        assert!(m.is_excluded(Path::new("/Users/test/app/node_modules")));
+        assert!(m.is_excluded(Path::new("/Users/test/app/node_modules/react/index.js")));
+        assert!(!m.is_excluded(Path::new("/Users/test/app/node_modules_backup")));
+        assert!(m.is_excluded(Path::new("/var/log/system.log")));
+        assert!(m.is_excluded(Path::new("/data/a/cache/blob")));
+        assert!(!m.is_excluded(Path::new("/data/a/b/cache")));
+        assert!(m.is_excluded(Path::new("/Users/test/app/Node_Modules")));
+        assert!(m.is_excluded(Path::new("/var/log/System.LOG")));
+    }
+
+    #[test]
+    fn test_negation_and_explanation() {
+        let m = matcher(&["**/node_modules", "!/work/keep/node_modules", "/secret"]);
+
+        let kept = m.explain(Path::new("/work/keep/node_modules/lib.js"));
+        assert!(!kept.excluded);
+        assert_eq!(kept.rule.as_deref(), Some("!/work/keep/node_modules"));
+
+        let excluded = m.explain(Path::new("/work/other/node_modules/lib.js"));
+        assert!(excluded.excluded);
+        assert_eq!(excluded.rule.as_deref(), Some("**/node_modules"));
+        assert_eq!(
+            excluded.matched_path,
+            Some(PathBuf::from("/work/other/node_modules"))
+        );
+
+        // Nothing inside an excluded folder can be re-included
+        let m = matcher(&["/secret", "!/secret/public"]);
+        assert!(m.is_excluded(Path::new("/secret/public/file")));
+
+        let none = m.explain(Path::new("/elsewhere"));
+        assert!(!none.excluded);
+        assert!(none.rule.is_none());
+    }
+
+    #[test]
+    fn test_excluded_inside() {
+        let m = matcher(&["/Users/test/Library/Caches/keep", "**/node_modules"]);
+        assert_eq!(
+            m.excluded_inside(Path::new("/Users/test/Library")),
+            Some("/Users/test/Library/Caches/keep")
+        );
+        assert_eq!(
+            m.excluded_inside(Path::new("/users/test/library")),
+            Some("/Users/test/Library/Caches/keep")
+        );
+        assert_eq!(
+            m.excluded_inside(Path::new("/Users/test/Library/Caches/keep")),
+            None
+        );
+        assert_eq!(m.excluded_inside(Path::new("/Users/other")), None);
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        assert!(ExclusionMatcher::validate_pattern("~/Projects/**/target").is_ok());
+        assert!(ExclusionMatcher::validate_pattern("node_modules").is_ok());
+        assert!(ExclusionMatcher::validate_pattern("/data/[abc").is_err());
+    }
+}
@@ -1,5 +1,12 @@
-use std::fs;
+use crate::scanner::progress::ScanContext;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
 /// Expand tilde (~) to home directory
 pub fn expand_tilde(path: &str) -> PathBuf {
@@ -15,35 +22,293 @@ pub fn expand_tilde(path: &str) -> PathBuf {
     PathBuf::from(path)
 }
 
-/// Get the size of a file or directory in bytes
-pub fn get_size(path: &Path) -> std::io::Result<u64> {
-    if path.is_file() {
-        Ok(fs::metadata(path)?.len())
-    } else if path.is_dir() {
-        get_dir_size(path)
-    } else {
-        Ok(0)
+/// Space taken up by a file or directory tree
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Sum of file lengths
+    pub logical: u64,
+    /// Bytes allocated on disk, including directories themselves
+    pub allocated: u64,
+    pub files: u64,
+    /// Entries that could not be read and are missing from the totals
+    pub unreadable: Vec<PathBuf>,
+}
+
+impl DiskUsage {
+    pub(crate) fn unreadable(path: &Path) -> Self {
+        Self {
+            unreadable: vec![path.to_path_buf()],
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn merge(mut self, other: Self) -> Self {
+        self.logical += other.logical;
+        self.allocated += other.allocated;
+        self.files += other.files;
+        self.unreadable.extend(other.unreadable);
+        self
     }
 }
 
-/// Get the size of a directory recursively
-pub fn get_dir_size(path: &Path) -> std::io::Result<u64> {
-    let mut total_size = 0u64;
+/// Measure a file or directory tree, walking directories in parallel
+///
+/// Hardlinked files are counted once, symlinks are never followed and other
+/// filesystems mounted inside `path` are skipped. A missing `path` is empty.
+pub fn disk_usage(path: &Path) -> DiskUsage {
+    disk_usage_with(path, &ScanContext::default())
+}
+
+/// [`disk_usage`], reporting each file to `ctx` and stopping early once it
+/// is cancelled
+pub fn disk_usage_with(path: &Path, ctx: &ScanContext) -> DiskUsage {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => UsageWalker::new(&metadata).entry(path, &metadata, ctx),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => DiskUsage::default(),
+        Err(_) => DiskUsage::unreadable(path),
+    }
+}
+
+/// Shared state of a walk that sizes a tree rooted on one device
+pub(crate) struct UsageWalker {
+    device: Option<u64>,
+    /// (dev, inode) of hardlinked files already counted
+    seen: Mutex<HashSet<(u64, u64)>>,
+}
 
-    if path.is_dir() {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
+impl UsageWalker {
+    /// Walker that stays on the device holding `root`
+    pub(crate) fn new(root: &Metadata) -> Self {
+        Self {
+            device: device_id(root),
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Whether a directory lies on another filesystem and must be skipped
+    pub(crate) fn crosses_device(&self, metadata: &Metadata) -> bool {
+        device_id(metadata) != self.device
+    }
 
-            if path.is_file() {
-                total_size += fs::metadata(&path)?.len();
-            } else if path.is_dir() {
-                total_size += get_dir_size(&path)?;
+    /// Usage of a non-directory entry, empty if it is a hardlink already counted
+    pub(crate) fn file(&self, metadata: &Metadata) -> DiskUsage {
+        if let Some(id) = hardlink_id(metadata) {
+            let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+            if !seen.insert(id) {
+                return DiskUsage::default();
             }
         }
+        DiskUsage {
+            logical: metadata.len(),
+            allocated: allocated_size(metadata),
+            files: 1,
+            unreadable: Vec::new(),
+        }
+    }
+
+    fn entry(&self, path: &Path, metadata: &Metadata, ctx: &ScanContext) -> DiskUsage {
+        if metadata.is_dir() {
+            return self.dir(path, metadata, ctx);
+        }
+        let usage = self.file(metadata);
+        ctx.visit(path, usage.logical);
+        usage
+    }
+
+    fn dir(&self, path: &Path, metadata: &Metadata, ctx: &ScanContext) -> DiskUsage {
+        if self.crosses_device(metadata) || ctx.is_cancelled() {
+            return DiskUsage::default();
+        }
+        let own = DiskUsage {
+            allocated: allocated_size(metadata),
+            ..DiskUsage::default()
+        };
+        let entries: Vec<_> = match fs::read_dir(path) {
+            Ok(entries) => entries.collect(),
+            // Removed while the walk was running, so there is nothing to count
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return own,
+            Err(_) => return own.merge(DiskUsage::unreadable(path)),
+        };
+
+        entries
+            .into_par_iter()
+            .map(|entry| {
+                let Ok(entry) = entry else {
+                    return DiskUsage::unreadable(path);
+                };
+                let entry_path = entry.path();
+                // DirEntry::metadata does not traverse symlinks
+                match entry.metadata() {
+                    Ok(metadata) => self.entry(&entry_path, &metadata, ctx),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => DiskUsage::default(),
+                    Err(_) => DiskUsage::unreadable(&entry_path),
+                }
+            })
+            .reduce(DiskUsage::default, DiskUsage::merge)
+            .merge(own)
     }
+}
+
+#[cfg(unix)]
+pub(crate) fn device_id(metadata: &Metadata) -> Option<u64> {
+    Some(metadata.dev())
+}
 
-    Ok(total_size)
+#[cfg(not(unix))]
+pub(crate) fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Identity of a file with more than one link, so it is only counted once
+#[cfg(unix)]
+pub(crate) fn hardlink_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hardlink_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Device and inode of a file, which stay the same across renames
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn allocated_size(metadata: &Metadata) -> u64 {
+    // st_blocks is always in 512-byte units
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub(crate) fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Get the logical size of a file or directory in bytes
+///
+/// Unreadable entries are left out rather than failing; use [`disk_usage`]
+/// to find out which ones.
+pub fn get_size(path: &Path) -> std::io::Result<u64> {
+    Ok(disk_usage(path).logical)
+}
+
+/// Get the logical size of a directory recursively
+pub fn get_dir_size(path: &Path) -> std::io::Result<u64> {
+    Ok(disk_usage(path).logical)
+}
+
+static WRITE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Write a file atomically by writing a sibling temp file and renaming it over `path`
+///
+/// Readers see either the old or the new contents, never a partial write.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent)?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Unique per write, so threads saving the same file never share a temp file
+    let sequence = WRITE_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = parent.join(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        sequence
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Move `from` to `to`, failing with `AlreadyExists` rather than replacing
+/// anything that appears at `to` in the meantime
+pub fn rename_no_replace(from: &Path, to: &Path) -> std::io::Result<()> {
+    match rename_exclusive(from, to) {
+        // The volume can't rename exclusively
+        Err(e) if is_unsupported(&e) => rename_checked(from, to),
+        result => result,
+    }
+}
+
+#[cfg(any(target_os = "macos", all(target_os = "linux", target_env = "gnu")))]
+fn rename_exclusive(from: &Path, to: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid NUL-terminated strings that outlive the call
+    #[cfg(target_os = "macos")]
+    let status = unsafe { libc::renamex_np(from.as_ptr(), to.as_ptr(), libc::RENAME_EXCL) };
+    // SAFETY: as above
+    #[cfg(target_os = "linux")]
+    let status = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            from.as_ptr(),
+            libc::AT_FDCWD,
+            to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", all(target_os = "linux", target_env = "gnu"))))]
+fn rename_exclusive(_from: &Path, _to: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+fn is_unsupported(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if matches!(
+        error.raw_os_error(),
+        Some(libc::EINVAL | libc::ENOSYS | libc::ENOTSUP)
+    ) {
+        return true;
+    }
+    error.kind() == std::io::ErrorKind::Unsupported
+}
+
+/// Files are hard linked then unlinked, which fails if `to` exists; folders
+/// can't be linked, so for them the check and the rename are separate
+fn rename_checked(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(from)?.is_dir() {
+        if fs::symlink_metadata(to).is_ok() {
+            return Err(std::io::ErrorKind::AlreadyExists.into());
+        }
+        return fs::rename(from, to);
+    }
+    fs::hard_link(from, to)?;
+    fs::remove_file(from)
 }
 
 /// Check if a path exists
@@ -90,6 +355,7 @@ mod tests {
     use super::*;
     use std::fs::File;
     use std::io::Write;
+    use std::sync::Arc;
     use tempfile::tempdir;
 
     #[test]
@@ -159,6 +425,83 @@ mod tests {
         assert_eq!(size, 20); // 9 + 11 bytes
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_usage_counts_hardlinks_once() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.bin"), vec![0u8; 4096]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::hard_link(dir.path().join("a.bin"), dir.path().join("sub/b.bin")).unwrap();
+        fs::write(dir.path().join("sub/c.txt"), b"hello").unwrap();
+
+        let usage = disk_usage(dir.path());
+        assert_eq!(usage.logical, 4096 + 5);
+        assert_eq!(usage.files, 2);
+        assert!(usage.allocated >= 4096);
+        assert!(usage.unreadable.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_usage_does_not_follow_symlinks() {
+        let outside = tempdir().unwrap();
+        fs::write(outside.path().join("big.bin"), vec![0u8; 10_000]).unwrap();
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("small.txt"), b"abc").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        let usage = disk_usage(dir.path());
+        // The link itself is counted, never what it points to
+        let link_len = fs::symlink_metadata(dir.path().join("link")).unwrap().len();
+        assert_eq!(usage.logical, 3 + link_len);
+        assert_eq!(usage.files, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_usage_collects_unreadable_paths() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("secret"), b"hidden").unwrap();
+        fs::write(dir.path().join("open.txt"), b"visible").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permissions do not apply when running as root
+        let readable = fs::read_dir(&locked).is_ok();
+        let usage = disk_usage(dir.path());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            return;
+        }
+
+        assert_eq!(usage.logical, 7);
+        assert_eq!(usage.unreadable, vec![locked]);
+    }
+
+    #[test]
+    fn test_disk_usage_with_reports_and_cancels() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.bin"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("sub/b.bin"), vec![0u8; 50]).unwrap();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&progress);
+        let ctx = ScanContext::new("usage").with_reporter(move |p| sink.lock().unwrap().push(p));
+        assert_eq!(disk_usage_with(dir.path(), &ctx).logical, 150);
+        ctx.finish();
+        let last = progress.lock().unwrap().pop().unwrap();
+        assert_eq!(last.current, 2);
+        assert_eq!(last.bytes_scanned, 150);
+
+        ctx.cancel();
+        assert_eq!(disk_usage_with(dir.path(), &ctx).logical, 0);
+    }
+
     #[test]
     fn test_path_exists() {
         let dir = tempdir().unwrap();
@@ -214,6 +557,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_atomic_replaces_contents() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested/state.json");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        // No temp files are left behind
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_write_atomic_never_interleaves() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let contents: Vec<Vec<u8>> = (0..8u8).map(|i| vec![b'a' + i; 64 * 1024]).collect();
+
+        std::thread::scope(|scope| {
+            for data in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_atomic(path, data).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert!(contents.contains(&fs::read(&path).unwrap()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
     #[test]
     fn test_get_size_special() {
         // Test a case that falls through is_file and is_dir (e.g. non-existent or special device, though non-existent would error on metadata)
@@ -229,4 +606,31 @@ mod tests {
         let size = get_size(&non_existent).unwrap();
         assert_eq!(size, 0);
     }
+
+    #[test]
+    fn test_rename_no_replace() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("from.txt");
+        let to = dir.path().join("to.txt");
+        fs::write(&from, b"old").unwrap();
+        fs::write(&to, b"new").unwrap();
+
+        for rename in [rename_no_replace, rename_checked] {
+            let error = rename(&from, &to).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+            assert_eq!(fs::read(&from).unwrap(), b"old");
+            assert_eq!(fs::read(&to).unwrap(), b"new");
+        }
+
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        let error = rename_checked(&folder, &to).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+
+        rename_no_replace(&from, &dir.path().join("moved.txt")).unwrap();
+        rename_checked(&folder, &dir.path().join("moved")).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read(dir.path().join("moved.txt")).unwrap(), b"old");
+        assert!(dir.path().join("moved").is_dir());
+    }
 }
@@ -1,53 +1,188 @@
+use crate::models::config::HashAlgorithm;
 use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
+use xxhash_rust::xxh3::Xxh3;
 
-/// Calculate SHA-256 hash of a file
-pub fn calculate_hash(path: &Path) -> io::Result<String> {
-    let mut file = File::open(path)?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 8192];
+/// Files up to this size are read with one buffer of their length; larger
+/// ones are read in chunks of it
+const MAX_READ_CHUNK: u64 = 8 * 1024 * 1024;
 
-    loop {
-        let count = file.read(&mut buffer)?;
-        if count == 0 {
-            break;
+/// Bytes read from each end of a file for the partial hash
+const PARTIAL_HASH_CHUNK: usize = 4096;
+
+/// Incremental digest over a file's contents
+pub trait Hasher {
+    fn update(&mut self, bytes: &[u8]);
+    /// The digest as lowercase hex
+    fn finish(self: Box<Self>) -> String;
+}
+
+impl Hasher for Sha256 {
+    fn update(&mut self, bytes: &[u8]) {
+        Digest::update(self, bytes);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        format!("{:x}", (*self).finalize())
+    }
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        blake3::Hasher::update(self, bytes);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        self.finalize().to_hex().to_string()
+    }
+}
+
+impl Hasher for Xxh3 {
+    fn update(&mut self, bytes: &[u8]) {
+        Xxh3::update(self, bytes);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        format!("{:032x}", self.digest128())
+    }
+}
+
+impl HashAlgorithm {
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            Self::Sha256 => Box::new(Sha256::new()),
+            Self::Blake3 => Box::new(blake3::Hasher::new()),
+            Self::Xxh3 => Box::new(Xxh3::new()),
         }
-        hasher.update(&buffer[..count]);
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    /// Name put in front of digests, so hashes from different algorithms never match
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+            Self::Xxh3 => "xxh3",
+        }
+    }
+
+    fn prefixed(self, digest: String) -> String {
+        format!("{}:{}", self.prefix(), digest)
+    }
+}
+
+/// Prefixed hash of the whole file, such as `blake3:…`
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    read_file(path, hasher.as_mut())?;
+    Ok(algorithm.prefixed(hasher.finish()))
+}
+
+/// Prefixed hash of the first and last 4KB of a file, for a quick comparison
+/// of large files before hashing them fully
+pub fn hash_file_partial(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    read_ends(path, hasher.as_mut())?;
+    Ok(algorithm.prefixed(hasher.finish()))
+}
+
+/// Prefixed hash of `bytes`
+pub fn hash_bytes(bytes: &[u8], algorithm: HashAlgorithm) -> String {
+    let mut hasher = algorithm.hasher();
+    hasher.update(bytes);
+    algorithm.prefixed(hasher.finish())
+}
+
+/// Calculate SHA-256 hash of a file
+pub fn calculate_hash(path: &Path) -> io::Result<String> {
+    let mut hasher = HashAlgorithm::Sha256.hasher();
+    read_file(path, hasher.as_mut())?;
+    Ok(hasher.finish())
 }
 
 /// Calculate partial SHA-256 hash (first 4KB + last 4KB)
 /// This is used for quick comparison of large files before full hashing
 pub fn calculate_partial_hash(path: &Path) -> io::Result<String> {
+    let mut hasher = HashAlgorithm::Sha256.hasher();
+    read_ends(path, hasher.as_mut())?;
+    Ok(hasher.finish())
+}
+
+fn read_file(path: &Path, hasher: &mut dyn Hasher) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    // Sized to the file so it usually takes one read; a file that grew since
+    // is read in further chunks
+    let mut buffer = vec![0; len.clamp(1, MAX_READ_CHUNK) as usize];
+    loop {
+        let count = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..count]);
+    }
+    Ok(())
+}
+
+fn read_ends(path: &Path, hasher: &mut dyn Hasher) -> io::Result<()> {
     let mut file = File::open(path)?;
     let metadata = file.metadata()?;
     let size = metadata.len();
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 4096];
+    let chunk = PARTIAL_HASH_CHUNK as u64;
+    // `take` keeps reading until the chunk is full or the file ends, where a
+    // single read may return less
+    let mut buffer = Vec::with_capacity(PARTIAL_HASH_CHUNK);
 
     // Read first 4KB
-    let count = file.read(&mut buffer)?;
-    hasher.update(&buffer[..count]);
+    (&mut file).take(chunk).read_to_end(&mut buffer)?;
+    hasher.update(&buffer);
 
     // If file is larger than 4KB, read last 4KB
-    if size > 4096 {
-        let seek_pos = if size > 8192 { size - 4096 } else { 4096 };
+    if size > chunk {
+        let seek_pos = if size > 2 * chunk {
+            size - chunk
+        } else {
+            chunk
+        };
 
         file.seek(SeekFrom::Start(seek_pos))?;
-        let count = file.read(&mut buffer)?;
-        hasher.update(&buffer[..count]);
+        buffer.clear();
+        (&mut file).take(chunk).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
     }
+    Ok(())
+}
 
-    Ok(format!("{:x}", hasher.finalize()))
+/// Merkle-style SHA-256 digest of a directory from `(name, hash)` pairs of
+/// its children
+///
+/// Child hashes are file hashes or digests of subdirectories, so two
+/// directories share a digest only if their whole trees match. The order of
+/// `children` does not matter.
+pub fn merkle_digest<'a>(children: impl IntoIterator<Item = (&'a OsStr, &'a str)>) -> String {
+    let mut children: Vec<_> = children.into_iter().collect();
+    children.sort_unstable();
+    let mut hasher = HashAlgorithm::Sha256.hasher();
+    for (name, hash) in children {
+        let name = name.as_encoded_bytes();
+        // Length prefixes keep names containing separators unambiguous
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        hasher.update(&(hash.len() as u64).to_le_bytes());
+        hasher.update(hash.as_bytes());
+    }
+    hasher.finish()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use std::io::Write;
     use tempfile::tempdir;
 
@@ -80,6 +215,21 @@ mod tests {
         assert_eq!(full_hash, partial_hash);
     }
 
+    #[test]
+    fn test_partial_hash_covers_files_up_to_two_chunks() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("medium.bin");
+        let data: Vec<u8> = (0..2 * PARTIAL_HASH_CHUNK - 100)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        fs::write(&file_path, &data).unwrap();
+
+        assert_eq!(
+            calculate_partial_hash(&file_path).unwrap(),
+            calculate_hash(&file_path).unwrap()
+        );
+    }
+
     #[test]
     fn test_calculate_partial_hash_large_file() {
         let dir = tempdir().unwrap();
@@ -95,4 +245,77 @@ mod tests {
         // Should calculate successfully
         assert_eq!(partial_hash.len(), 64);
     }
+
+    #[test]
+    fn test_merkle_digest() {
+        let a = OsStr::new("a.txt");
+        let b = OsStr::new("b");
+        let digest = merkle_digest([(a, "1111"), (b, "2222")]);
+        assert_eq!(digest.len(), 64);
+        assert_eq!(digest, merkle_digest([(b, "2222"), (a, "1111")]));
+        assert_ne!(digest, merkle_digest([(a, "1111"), (b, "2223")]));
+        assert_ne!(
+            digest,
+            merkle_digest([(OsStr::new("a.txt2"), "222"), (b, "1111")])
+        );
+    }
+
+    #[test]
+    fn test_algorithms_are_prefixed() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, b"Hello, World!").unwrap();
+
+        assert_eq!(
+            hash_file(&file_path, HashAlgorithm::Sha256).unwrap(),
+            format!("sha256:{}", calculate_hash(&file_path).unwrap())
+        );
+        assert_eq!(
+            hash_file(&file_path, HashAlgorithm::Blake3).unwrap(),
+            format!("blake3:{}", blake3::hash(b"Hello, World!").to_hex())
+        );
+        assert_eq!(
+            hash_file(&file_path, HashAlgorithm::Xxh3).unwrap(),
+            format!(
+                "xxh3:{:032x}",
+                xxhash_rust::xxh3::xxh3_128(b"Hello, World!")
+            )
+        );
+        // Small files are covered by the partial hash
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Xxh3,
+        ] {
+            assert_eq!(
+                hash_file_partial(&file_path, algorithm).unwrap(),
+                hash_file(&file_path, algorithm).unwrap()
+            );
+            assert_eq!(
+                hash_bytes(b"Hello, World!", algorithm),
+                hash_file(&file_path, algorithm).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_files_read_in_chunks_hash_like_whole_ones() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("large.bin");
+        let data: Vec<u8> = (0..MAX_READ_CHUNK + 12_345)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        fs::write(&file_path, &data).unwrap();
+
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Xxh3,
+        ] {
+            assert_eq!(
+                hash_file(&file_path, algorithm).unwrap(),
+                hash_bytes(&data, algorithm)
+            );
+        }
+    }
 }
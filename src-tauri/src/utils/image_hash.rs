@@ -0,0 +1,97 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageReader};
+use std::path::Path;
+
+/// A decoded image reduced to what similar-image detection compares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageFingerprint {
+    pub width: u32,
+    pub height: u32,
+    /// 64-bit difference hash, see [`dhash`]
+    pub hash: u64,
+}
+
+/// Thumbnails whose brightness spans less than this are treated as blank
+const MIN_CONTRAST: u8 = 8;
+
+/// Decode the image at `path`, trusting its contents over its extension
+///
+/// Blank images have no fingerprint, see [`dhash`].
+pub fn fingerprint(path: &Path) -> Result<Option<ImageFingerprint>, ImageError> {
+    let image = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    Ok(dhash(&image).map(|hash| ImageFingerprint {
+        width: image.width(),
+        height: image.height(),
+        hash,
+    }))
+}
+
+/// Difference hash: one bit per pair of neighbouring pixels on a 9x8
+/// grayscale thumbnail, set where brightness increases to the right
+///
+/// Resizing, re-encoding and small colour changes flip few bits, so similar
+/// images have hashes a small Hamming distance apart. Returns `None` for a
+/// thumbnail with no brightness gradient, since every blank image would
+/// share the same hash.
+pub fn dhash(image: &DynamicImage) -> Option<u64> {
+    let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let (min, max) = thumbnail
+        .pixels()
+        .fold((u8::MAX, u8::MIN), |(min, max), p| {
+            (min.min(p[0]), max.max(p[0]))
+        });
+    if max.saturating_sub(min) < MIN_CONTRAST {
+        return None;
+    }
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = thumbnail.get_pixel(x, y)[0];
+            let right = thumbnail.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    Some(hash)
+}
+
+/// Number of bits in which two hashes differ
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            Luma([((x * 255 / width + y * 64 / height) % 256) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_dhash_survives_resizing() {
+        let image = gradient(640, 480);
+        let hash = dhash(&image).unwrap();
+        let resized = image.resize_exact(160, 120, FilterType::Lanczos3);
+        assert!(hamming_distance(hash, dhash(&resized).unwrap()) <= 2);
+
+        let flipped = image.fliph();
+        assert!(hamming_distance(hash, dhash(&flipped).unwrap()) > 32);
+    }
+
+    #[test]
+    fn test_blank_image_has_no_dhash() {
+        let blank = DynamicImage::ImageLuma8(GrayImage::from_pixel(100, 100, Luma([200])));
+        assert_eq!(dhash(&blank), None);
+    }
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(0b1011, 0b0010), 2);
+        assert_eq!(hamming_distance(0, u64::MAX), 64);
+    }
+}
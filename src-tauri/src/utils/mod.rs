@@ -1,4 +1,9 @@
+pub mod app_dirs;
+pub mod config_store;
+pub mod exclusions;
 pub mod format;
 pub mod fs;
 pub mod hash;
+pub mod image_hash;
 pub mod permissions;
+pub mod volumes;
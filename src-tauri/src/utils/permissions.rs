@@ -1,6 +1,30 @@
+use crate::analyzer::app_registry::AppRegistry;
+use crate::models::scan_result::{AccessStatus, LocationAccess, PermissionReport, ScanKind};
+use crate::scanner::orphan_scanner::ORPHAN_SCAN_LOCATIONS;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 #[cfg(target_os = "macos")]
 use std::process::Command;
 
+/// Folders inside the home directory that macOS guards behind privacy
+/// prompts or Full Disk Access, which whole-home walks pass through
+const PRIVACY_PROTECTED_PATHS: &[&str] = &[
+    "Desktop",
+    "Documents",
+    "Downloads",
+    "Library/Mail",
+    "Library/Messages",
+    "Library/Safari",
+];
+
+/// A location some scan reads, and the scans that need it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationProbe {
+    pub path: PathBuf,
+    pub affects: Vec<ScanKind>,
+}
+
 /// Check if the application has Full Disk Access (FDA)
 ///
 /// On macOS, we can check this by trying to read a directory that requires FDA,
@@ -34,6 +58,81 @@ pub fn check_full_disk_access() -> bool {
     }
 }
 
+/// Every location the scanners read for `home`, with `scan_roots` being the
+/// roots whole-disk scans walk (see `volumes::scan_roots`)
+pub fn default_probes(home: &Path, scan_roots: &[PathBuf]) -> Vec<LocationProbe> {
+    let mut probes: Vec<LocationProbe> = Vec::new();
+    let mut add = |path: PathBuf, kind: ScanKind| match probes.iter_mut().find(|p| p.path == path) {
+        Some(probe) if !probe.affects.contains(&kind) => probe.affects.push(kind),
+        Some(_) => {}
+        None => probes.push(LocationProbe {
+            path,
+            affects: vec![kind],
+        }),
+    };
+
+    add(home.join("Library/Caches"), ScanKind::Caches);
+    add(home.join("Library/Logs"), ScanKind::Caches);
+    add(std::env::temp_dir(), ScanKind::Caches);
+    for (relative, _) in ORPHAN_SCAN_LOCATIONS {
+        add(home.join(relative), ScanKind::OrphanedApps);
+    }
+    for root in AppRegistry::default_roots(home) {
+        add(root, ScanKind::OrphanedApps);
+    }
+
+    for root in scan_roots {
+        if root == home {
+            for relative in PRIVACY_PROTECTED_PATHS {
+                add(home.join(relative), ScanKind::LargeFiles);
+            }
+        } else {
+            add(root.clone(), ScanKind::LargeFiles);
+        }
+    }
+    // Duplicate scans are pointed at folders per scan; the protected ones are
+    // where they are most likely to be refused
+    for relative in PRIVACY_PROTECTED_PATHS {
+        add(home.join(relative), ScanKind::Duplicates);
+    }
+
+    probes
+}
+
+/// Whether `path` can be read: a folder listed or a file opened
+pub fn probe_access(path: &Path) -> AccessStatus {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path).map(|_| ()),
+        Ok(_) => fs::File::open(path).map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => AccessStatus::Readable,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => AccessStatus::Missing,
+        Err(_) => AccessStatus::Denied,
+    }
+}
+
+/// Probe each location in `probes`
+pub fn probe_locations(probes: &[LocationProbe]) -> Vec<LocationAccess> {
+    probes
+        .iter()
+        .map(|probe| LocationAccess {
+            path: probe.path.clone(),
+            status: probe_access(&probe.path),
+            affects: probe.affects.clone(),
+        })
+        .collect()
+}
+
+/// Full Disk Access status along with access to each location in `probes`
+pub fn permission_report(probes: &[LocationProbe]) -> PermissionReport {
+    PermissionReport {
+        full_disk_access: check_full_disk_access(),
+        locations: probe_locations(probes),
+    }
+}
+
 /// Open the System Settings to the Full Disk Access page
 pub fn open_full_disk_access_settings() {
     #[cfg(target_os = "macos")]
@@ -54,4 +153,75 @@ mod tests {
         // But we can ensure it doesn't panic
         let _ = check_full_disk_access();
     }
+
+    #[test]
+    fn test_default_probes_merge_shared_locations() {
+        let home = Path::new("/Users/test");
+        let probes = default_probes(home, &[home.to_path_buf()]);
+        let caches = probes
+            .iter()
+            .find(|p| p.path == home.join("Library/Caches"))
+            .unwrap();
+        assert_eq!(
+            caches.affects,
+            vec![ScanKind::Caches, ScanKind::OrphanedApps]
+        );
+        assert_eq!(
+            probes
+                .iter()
+                .filter(|p| p.path == home.join("Library/Caches"))
+                .count(),
+            1
+        );
+
+        let large_files = |probes: &[LocationProbe]| -> Vec<PathBuf> {
+            probes
+                .iter()
+                .filter(|p| p.affects.contains(&ScanKind::LargeFiles))
+                .map(|p| p.path.clone())
+                .collect()
+        };
+        assert!(large_files(&probes).contains(&home.join("Documents")));
+
+        let custom = default_probes(home, &[PathBuf::from("/Volumes/Media")]);
+        assert_eq!(large_files(&custom), vec![PathBuf::from("/Volumes/Media")]);
+
+        let external = default_probes(
+            home,
+            &[home.to_path_buf(), PathBuf::from("/Volumes/Backup")],
+        );
+        assert!(large_files(&external).contains(&PathBuf::from("/Volumes/Backup")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_probe_locations_reports_each_status() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let open = dir.path().join("open");
+        let locked = dir.path().join("locked");
+        fs::create_dir(&open).unwrap();
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let probe = |path: &Path| LocationProbe {
+            path: path.to_path_buf(),
+            affects: vec![ScanKind::Caches],
+        };
+        let report = probe_locations(&[
+            probe(&open),
+            probe(&dir.path().join("missing")),
+            probe(&locked),
+        ]);
+        // Permissions do not apply when running as root
+        let enforced = fs::read_dir(&locked).is_err();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(report[0].status, AccessStatus::Readable);
+        assert_eq!(report[1].status, AccessStatus::Missing);
+        if enforced {
+            assert_eq!(report[2].status, AccessStatus::Denied);
+        }
+    }
 }
@@ -0,0 +1,186 @@
+use crate::models::config::ScanLocations;
+use crate::utils::fs::expand_tilde;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use sysinfo::Disks;
+
+/// Where APFS mounts the writable half of the startup disk; `/` is the
+/// read-only, sealed system snapshot
+pub const APFS_DATA_MOUNT: &str = "/System/Volumes/Data";
+
+/// Where macOS mounts volumes other than the startup disk
+const EXTERNAL_MOUNT_ROOT: &str = "/Volumes";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeInfo {
+    pub name: String,
+    pub mount_point: PathBuf,
+    pub file_system: String,
+    pub is_removable: bool,
+    pub is_read_only: bool,
+    pub total_space: u64,
+    pub available_space: u64,
+    pub used_space: u64,
+}
+
+/// Every mounted volume
+pub fn list_volumes() -> Vec<VolumeInfo> {
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .map(|disk| VolumeInfo {
+            name: disk.name().to_string_lossy().to_string(),
+            mount_point: disk.mount_point().to_path_buf(),
+            file_system: disk.file_system().to_string_lossy().to_string(),
+            is_removable: disk.is_removable(),
+            is_read_only: disk.is_read_only(),
+            total_space: disk.total_space(),
+            available_space: disk.available_space(),
+            used_space: disk.total_space().saturating_sub(disk.available_space()),
+        })
+        .collect()
+}
+
+/// The volume holding the user's data
+///
+/// On APFS that is the Data volume rather than the sealed system volume at
+/// `/`, which reports only the system's own usage. Falls back to `/`, then to
+/// the first fixed volume, then to any volume.
+pub fn root_volume(volumes: &[VolumeInfo]) -> Option<&VolumeInfo> {
+    let mounted_at = |mount: &str| volumes.iter().find(|v| v.mount_point == Path::new(mount));
+    mounted_at(APFS_DATA_MOUNT)
+        .or_else(|| mounted_at("/"))
+        .or_else(|| volumes.iter().find(|v| !v.is_removable))
+        .or_else(|| volumes.first())
+}
+
+/// Writable volumes other than the startup disk, such as USB drives and
+/// disks mounted under `/Volumes`
+pub fn external_volumes(volumes: &[VolumeInfo]) -> Vec<&VolumeInfo> {
+    volumes
+        .iter()
+        .filter(|v| !v.is_read_only)
+        .filter(|v| v.is_removable || v.mount_point.starts_with(EXTERNAL_MOUNT_ROOT))
+        .filter(|v| v.mount_point != Path::new("/") && !v.mount_point.starts_with("/System"))
+        .collect()
+}
+
+/// Folders a whole-disk scan should walk: `custom_scan_paths` when any are
+/// configured and `home` otherwise, plus external volumes when
+/// `include_external_volumes` is set
+pub fn scan_roots(locations: &ScanLocations, home: &Path, volumes: &[VolumeInfo]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = if locations.custom_scan_paths.is_empty() {
+        vec![home.to_path_buf()]
+    } else {
+        locations
+            .custom_scan_paths
+            .iter()
+            .map(|p| expand_tilde(&p.to_string_lossy()))
+            .collect()
+    };
+    if locations.include_external_volumes {
+        for volume in external_volumes(volumes) {
+            if !roots.iter().any(|r| volume.mount_point.starts_with(r)) {
+                roots.push(volume.mount_point.clone());
+            }
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(mount_point: &str, is_removable: bool, is_read_only: bool) -> VolumeInfo {
+        VolumeInfo {
+            name: mount_point.to_string(),
+            mount_point: PathBuf::from(mount_point),
+            file_system: "apfs".to_string(),
+            is_removable,
+            is_read_only,
+            total_space: 100,
+            available_space: 40,
+            used_space: 60,
+        }
+    }
+
+    fn mac_volumes() -> Vec<VolumeInfo> {
+        vec![
+            volume("/", false, true),
+            volume("/System/Volumes/VM", false, false),
+            volume(APFS_DATA_MOUNT, false, false),
+            volume("/Volumes/Backup", false, false),
+            volume("/Volumes/USB", true, false),
+            volume("/Volumes/Installer", true, true),
+        ]
+    }
+
+    #[test]
+    fn test_root_volume_prefers_apfs_data() {
+        let volumes = mac_volumes();
+        assert_eq!(
+            root_volume(&volumes).unwrap().mount_point,
+            PathBuf::from(APFS_DATA_MOUNT)
+        );
+
+        let linux = vec![volume("/boot", false, false), volume("/", false, false)];
+        assert_eq!(root_volume(&linux).unwrap().mount_point, PathBuf::from("/"));
+
+        let usb_only = vec![
+            volume("/media/usb", true, false),
+            volume("/data", false, false),
+        ];
+        assert_eq!(
+            root_volume(&usb_only).unwrap().mount_point,
+            PathBuf::from("/data")
+        );
+        assert!(root_volume(&[]).is_none());
+    }
+
+    #[test]
+    fn test_external_volumes() {
+        let volumes = mac_volumes();
+        let external: Vec<&Path> = external_volumes(&volumes)
+            .iter()
+            .map(|v| v.mount_point.as_path())
+            .collect();
+        assert_eq!(
+            external,
+            vec![Path::new("/Volumes/Backup"), Path::new("/Volumes/USB")]
+        );
+    }
+
+    #[test]
+    fn test_scan_roots() {
+        let volumes = mac_volumes();
+        let home = Path::new("/Users/test");
+        let mut locations = ScanLocations {
+            include_external_volumes: false,
+            custom_scan_paths: Vec::new(),
+        };
+        assert_eq!(
+            scan_roots(&locations, home, &volumes),
+            vec![home.to_path_buf()]
+        );
+
+        locations.include_external_volumes = true;
+        locations.custom_scan_paths = vec![PathBuf::from("/Volumes/USB/Movies")];
+        assert_eq!(
+            scan_roots(&locations, home, &volumes),
+            vec![
+                PathBuf::from("/Volumes/USB/Movies"),
+                PathBuf::from("/Volumes/Backup"),
+                PathBuf::from("/Volumes/USB"),
+            ]
+        );
+
+        locations.custom_scan_paths = vec![PathBuf::from("/Volumes")];
+        assert_eq!(
+            scan_roots(&locations, home, &volumes),
+            vec![PathBuf::from("/Volumes")]
+        );
+    }
+}
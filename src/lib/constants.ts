@@ -41,36 +41,6 @@ export const MEDIA_TYPES = {
 
 export type MediaType = keyof typeof MEDIA_TYPES;
 
-// Developer tool indicators
-export const DEVELOPER_TOOLS = {
-  Xcode: {
-    paths: ["/Applications/Xcode.app", "~/Library/Developer"],
-    cachePaths: ["~/Library/Developer/Xcode/DerivedData"],
-  },
-  Homebrew: {
-    paths: ["/opt/homebrew", "/usr/local/Homebrew"],
-    cachePaths: ["~/Library/Caches/Homebrew"],
-  },
-  NodeNpm: {
-    paths: ["~/.npm", "~/.nvm"],
-    cachePaths: ["~/.npm/_cacache", "~/node_modules"],
-  },
-  Rust: {
-    paths: ["~/.cargo", "~/.rustup"],
-    cachePaths: ["~/.cargo/registry"],
-  },
-  Python: {
-    paths: ["~/.pyenv", "~/.virtualenvs"],
-    cachePaths: ["~/.cache/pip"],
-  },
-  Docker: {
-    paths: ["/Applications/Docker.app", "~/.docker"],
-    cachePaths: ["~/Library/Containers/com.docker.docker"],
-  },
-} as const;
-
-export type DeveloperToolType = keyof typeof DEVELOPER_TOOLS;
-
 // UI Constants
 export const SIDEBAR_WIDTH = 224; // 14rem
 export const HEADER_HEIGHT = 48;
@@ -141,7 +141,7 @@ export interface CustomProfile {
 export interface AutoCleanConfig {
   enabled: boolean;
   schedule: AutoCleanSchedule;
-  categories: string[]; 
+  categories: CacheCategoryType[];
   minAgeDays: number;
 }
 